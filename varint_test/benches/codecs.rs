@@ -0,0 +1,103 @@
+//Criterion benchmarks of encoding, decoding and next_geq for every codec, on the same random list.
+//Run with cargo bench, optionally filtered by name, e.g. cargo bench -- decode
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use varint_test::{
+    codec::{encode, IntSequenceCodec, IntSequenceFactory},
+    elias_fano::EliasFanoFactory,
+    simple16::Simple16Factory,
+    varint_gb::{DescriptorTable, VarintGBFactory},
+    varint_su::VarintSUFactory,
+};
+
+const LEN: usize = 100000;
+
+fn reference_vector() -> Vec<u32> {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut value = 0;
+    (0..LEN)
+        .map(|_| {
+            value += rng.gen_range(1..1000);
+            value
+        })
+        .collect()
+}
+
+fn bench_codec<F: IntSequenceFactory>(
+    c: &mut Criterion,
+    name: &str,
+    make_factory: impl Fn() -> F,
+    values: &[u32],
+) {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(values.len() as u64));
+
+    group.bench_function("encode", |b| {
+        b.iter(|| encode(make_factory(), black_box(values)))
+    });
+
+    let seq = encode(make_factory(), values);
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            for val in seq.values() {
+                black_box(val);
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_encode_decode(c: &mut Criterion) {
+    let values = reference_vector();
+    bench_codec(c, "gb", VarintGBFactory::new, &values);
+    bench_codec(c, "gb-blocks", VarintGBFactory::new_block_aligned, &values);
+    bench_codec(c, "su", VarintSUFactory::new, &values);
+    bench_codec(c, "s16", Simple16Factory::new, &values);
+    bench_codec(c, "ef", EliasFanoFactory::new, &values);
+}
+
+//Seeks through the list in steps, so every call skips about step values
+fn bench_next_geq(c: &mut Criterion) {
+    let values = reference_vector();
+    let shuffle_table = DescriptorTable::new();
+    let gb = encode(VarintGBFactory::new_block_aligned(), &values);
+    let su = encode(VarintSUFactory::new_with_restarts(128), &values);
+
+    let mut group = c.benchmark_group("next_geq");
+    for step in [4, 64, 1024] {
+        let targets: Vec<u32> = values.iter().step_by(step).map(|val| val + 1).collect();
+        group.throughput(Throughput::Elements(targets.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("gb-blocks", step),
+            &targets,
+            |b, targets| {
+                b.iter(|| {
+                    let mut iter = gb.iter_values(&shuffle_table);
+                    for target in targets.iter() {
+                        black_box(iter.next_geq(*target));
+                    }
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("su-restarts", step),
+            &targets,
+            |b, targets| {
+                b.iter(|| {
+                    let mut iter = su.iter();
+                    for target in targets.iter() {
+                        black_box(iter.next_geq(*target));
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode_decode, bench_next_geq);
+criterion_main!(benches);
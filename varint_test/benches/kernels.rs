@@ -0,0 +1,136 @@
+//Criterion benchmarks of the GB decode kernels alone: the byte shuffles of every backend the CPU
+//supports, and the prefix sums that turn gaps into values. The groups are synthetic and small enough
+//to stay in L1, so the numbers show the kernels, not the iterators or memory around them.
+//Run with cargo bench --bench kernels, optionally filtered by name, e.g. -- shuffle
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use varint_test::{
+    simd::{prefix_sum_u16, shuffle_bytes, shuffle_bytes_pair, Backend, Shuffle},
+    varint_gb::{delta_chunk_to_value_chunk, DescriptorTable},
+};
+
+//256 groups of 16 bytes and their shuffles take 8 KB
+const GROUPS: usize = 256;
+
+const BACKENDS: [Backend; 4] = [
+    Backend::Scalar,
+    Backend::Ssse3,
+    Backend::Avx2,
+    Backend::Neon,
+];
+
+//Random bytes for every group, with the shuffle of a random descriptor
+fn groups() -> Vec<([u8; 16], Shuffle)> {
+    let mut rng = StdRng::seed_from_u64(7);
+    let table = DescriptorTable::new();
+    (0..GROUPS)
+        .map(|_| {
+            let bytes = rng.gen::<[u8; 16]>();
+            (bytes, table.get_shuffle_for_descriptor(rng.gen()))
+        })
+        .collect()
+}
+
+fn supported_backends() -> impl Iterator<Item = Backend> {
+    BACKENDS
+        .into_iter()
+        .filter(|backend| backend.is_supported())
+}
+
+fn bench_shuffle(c: &mut Criterion) {
+    let groups = groups();
+
+    let mut group = c.benchmark_group("shuffle");
+    group.throughput(Throughput::Elements(4 * GROUPS as u64));
+    for backend in supported_backends() {
+        group.bench_function(BenchmarkId::new("single", format!("{backend:?}")), |b| {
+            b.iter(|| {
+                for (bytes, shuffle) in groups.iter() {
+                    black_box(unsafe { shuffle_bytes(backend, bytes, shuffle) });
+                }
+            })
+        });
+        group.bench_function(BenchmarkId::new("pair", format!("{backend:?}")), |b| {
+            b.iter(|| {
+                for pair in groups.chunks_exact(2) {
+                    black_box(unsafe {
+                        shuffle_bytes_pair(
+                            backend,
+                            [&pair[0].0, &pair[1].0],
+                            [&pair[0].1, &pair[1].1],
+                        )
+                    });
+                }
+            })
+        });
+
+        //Through the table, as the decoders call it
+        let table = DescriptorTable::with_backend(backend);
+        group.bench_function(
+            BenchmarkId::new("decode_chunk", format!("{backend:?}")),
+            |b| {
+                b.iter(|| {
+                    for (bytes, shuffle) in groups.iter() {
+                        black_box(table.decode_chunk(bytes, shuffle));
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_prefix_sum(c: &mut Criterion) {
+    let groups = groups();
+    let chunks: Vec<[u32; 4]> = groups
+        .iter()
+        .map(|(bytes, _)| unsafe { std::mem::transmute::<[u8; 16], [u32; 4]>(*bytes) })
+        .collect();
+    let chunks_u16: Vec<[u16; 8]> = groups
+        .iter()
+        .map(|(bytes, _)| unsafe { std::mem::transmute::<[u8; 16], [u16; 8]>(*bytes) })
+        .collect();
+
+    let mut group = c.benchmark_group("prefix_sum");
+    group.throughput(Throughput::Elements(4 * GROUPS as u64));
+    group.bench_function("u32-scalar", |b| {
+        b.iter(|| {
+            let mut last = 0;
+            for chunk in chunks.iter() {
+                let mut values = *chunk;
+                delta_chunk_to_value_chunk(&mut values, last);
+                last = black_box(values)[3];
+            }
+        })
+    });
+
+    group.throughput(Throughput::Elements(8 * GROUPS as u64));
+    group.bench_function("u16-simd", |b| {
+        b.iter(|| {
+            let mut last = 0;
+            for deltas in chunks_u16.iter() {
+                last = black_box(prefix_sum_u16(*deltas, last))[7];
+            }
+        })
+    });
+    group.bench_function("u16-scalar", |b| {
+        b.iter(|| {
+            let mut last: u16 = 0;
+            for deltas in chunks_u16.iter() {
+                let mut values = *deltas;
+                for value in values.iter_mut() {
+                    last = last.wrapping_add(*value);
+                    *value = last;
+                }
+                black_box(values);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_shuffle, bench_prefix_sum);
+criterion_main!(benches);
@@ -0,0 +1,365 @@
+/*
+Benchmarks of the codecs on posting lists, for the bench subcommand of the binary.
+
+Lists are loaded from a file in one of two formats:
+- Text: one value per line, with an empty line between lists
+- Binary: for every list, its length and then its values, all as little endian u32
+
+Every list must be strictly increasing. Each codec encodes every list, then decodes every list
+reps times, and reports its size as bits per integer next to its throughput.
+
+Codec only has the codecs whose features are enabled, so a codec left out of the build is not
+found by from_name, and is not in ALL.
+*/
+
+use std::{
+    fmt,
+    hint::black_box,
+    io::{self, BufRead, Write},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "ef")]
+use crate::elias_fano::EliasFanoFactory;
+#[cfg(feature = "s16")]
+use crate::simple16::Simple16Factory;
+#[cfg(feature = "gb")]
+use crate::varint_gb::VarintGBFactory;
+#[cfg(feature = "su")]
+use crate::varint_su::VarintSUFactory;
+use crate::{
+    codec::{encode, IntSequenceCodec, IntSequenceFactory},
+    frame::{FrameError, FrameReader},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Codec {
+    #[cfg(feature = "gb")]
+    Gb,
+    //GB with block headers every BLOCK_LEN values
+    #[cfg(feature = "gb")]
+    GbBlocks,
+    //GB with long runs of consecutive values written as escape groups
+    #[cfg(feature = "gb")]
+    GbRuns,
+    #[cfg(feature = "su")]
+    Su,
+    #[cfg(feature = "s16")]
+    Simple16,
+    #[cfg(feature = "ef")]
+    EliasFano,
+}
+
+impl Codec {
+    pub const ALL: &'static [Codec] = &[
+        #[cfg(feature = "gb")]
+        Codec::Gb,
+        #[cfg(feature = "gb")]
+        Codec::GbBlocks,
+        #[cfg(feature = "gb")]
+        Codec::GbRuns,
+        #[cfg(feature = "su")]
+        Codec::Su,
+        #[cfg(feature = "s16")]
+        Codec::Simple16,
+        #[cfg(feature = "ef")]
+        Codec::EliasFano,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gb")]
+            Codec::Gb => "gb",
+            #[cfg(feature = "gb")]
+            Codec::GbBlocks => "gb-blocks",
+            #[cfg(feature = "gb")]
+            Codec::GbRuns => "gb-runs",
+            #[cfg(feature = "su")]
+            Codec::Su => "su",
+            #[cfg(feature = "s16")]
+            Codec::Simple16 => "s16",
+            #[cfg(feature = "ef")]
+            Codec::EliasFano => "ef",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|codec| codec.name() == name)
+    }
+
+    //Simple-16 cannot store gaps of 2^28 or more
+    #[allow(unreachable_patterns, unused_variables)]
+    pub fn supports(self, lists: &[Vec<u32>]) -> bool {
+        match self {
+            #[cfg(feature = "s16")]
+            Codec::Simple16 => lists.iter().all(|list| {
+                let mut top = 0;
+                list.iter().all(|val| {
+                    let gap = val - top;
+                    top = *val;
+                    gap < 1 << 28
+                })
+            }),
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BenchResult {
+    pub codec: Codec,
+    pub lists: usize,
+    pub integers: usize,
+    pub bytes: usize,
+    pub reps: usize,
+    pub encode_time: Duration,
+    //Time to decode every list reps times
+    pub decode_time: Duration,
+}
+
+impl BenchResult {
+    pub fn bits_per_integer(&self) -> f64 {
+        self.bytes as f64 * 8.0 / self.integers as f64
+    }
+
+    //Millions of integers encoded per second
+    pub fn encode_rate(&self) -> f64 {
+        self.integers as f64 / self.encode_time.as_secs_f64() / 1e6
+    }
+
+    //Millions of integers decoded per second
+    pub fn decode_rate(&self) -> f64 {
+        (self.integers * self.reps) as f64 / self.decode_time.as_secs_f64() / 1e6
+    }
+}
+
+pub fn run(codec: Codec, lists: &[Vec<u32>], reps: usize) -> BenchResult {
+    match codec {
+        #[cfg(feature = "gb")]
+        Codec::Gb => run_codec(codec, VarintGBFactory::new, lists, reps),
+        #[cfg(feature = "gb")]
+        Codec::GbBlocks => run_codec(codec, VarintGBFactory::new_block_aligned, lists, reps),
+        #[cfg(feature = "gb")]
+        Codec::GbRuns => run_codec(codec, VarintGBFactory::new_with_run_escapes, lists, reps),
+        #[cfg(feature = "su")]
+        Codec::Su => run_codec(codec, VarintSUFactory::new, lists, reps),
+        #[cfg(feature = "s16")]
+        Codec::Simple16 => run_codec(codec, Simple16Factory::new, lists, reps),
+        #[cfg(feature = "ef")]
+        Codec::EliasFano => run_codec(codec, EliasFanoFactory::new, lists, reps),
+    }
+}
+
+fn run_codec<F: IntSequenceFactory>(
+    codec: Codec,
+    make_factory: impl Fn() -> F,
+    lists: &[Vec<u32>],
+    reps: usize,
+) -> BenchResult {
+    let encode_start = Instant::now();
+    let seqs: Vec<F::Output> = lists
+        .iter()
+        .map(|list| encode(make_factory(), list))
+        .collect();
+    let encode_time = encode_start.elapsed();
+
+    let decode_start = Instant::now();
+    for _ in 0..reps {
+        for seq in seqs.iter() {
+            for val in seq.values() {
+                black_box(val);
+            }
+        }
+    }
+    let decode_time = decode_start.elapsed();
+
+    BenchResult {
+        codec,
+        lists: lists.len(),
+        integers: lists.iter().map(|list| list.len()).sum(),
+        bytes: seqs.iter().map(|seq| seq.size_in_bytes()).sum(),
+        reps,
+        encode_time,
+        decode_time,
+    }
+}
+
+//How often gb-runs writes an escape group for the lists: the number of escaped runs, and the
+//number of values in them
+#[cfg(feature = "gb")]
+pub fn escaped_runs(lists: &[Vec<u32>]) -> (usize, usize) {
+    lists
+        .iter()
+        .map(|list| encode(VarintGBFactory::new_with_run_escapes(), list).escaped_runs())
+        .fold((0, 0), |(runs, values), (list_runs, list_values)| {
+            (runs + list_runs, values + list_values)
+        })
+}
+
+pub fn write_csv<W: Write>(results: &[BenchResult], writer: &mut W) -> io::Result<()> {
+    writeln!(
+        writer,
+        "codec,lists,integers,bytes,bits_per_integer,reps,encode_ms,decode_ms,encode_mis,decode_mis"
+    )?;
+    for result in results.iter() {
+        writeln!(
+            writer,
+            "{},{},{},{},{:.3},{},{:.3},{:.3},{:.2},{:.2}",
+            result.codec,
+            result.lists,
+            result.integers,
+            result.bytes,
+            result.bits_per_integer(),
+            result.reps,
+            result.encode_time.as_secs_f64() * 1e3,
+            result.decode_time.as_secs_f64() * 1e3,
+            result.encode_rate(),
+            result.decode_rate()
+        )?;
+    }
+    Ok(())
+}
+
+//Reads lists in the text format
+pub fn read_text<R: BufRead>(reader: R) -> io::Result<Vec<Vec<u32>>> {
+    let mut lists = Vec::new();
+    let mut list = Vec::new();
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            if !list.is_empty() {
+                lists.push(std::mem::take(&mut list));
+            }
+            continue;
+        }
+
+        let val = line.parse().map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {err}", line_number + 1),
+            )
+        })?;
+        list.push(val);
+    }
+    if !list.is_empty() {
+        lists.push(list);
+    }
+
+    check_increasing(&lists)?;
+    Ok(lists)
+}
+
+//Reads lists in the binary format
+pub fn read_binary(bytes: &[u8]) -> io::Result<Vec<Vec<u32>>> {
+    let lists =
+        parse_binary(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    check_increasing(&lists)?;
+    Ok(lists)
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<Vec<Vec<u32>>, FrameError> {
+    let mut reader = FrameReader::new(bytes);
+    let mut lists = Vec::new();
+    while reader.remaining() > 0 {
+        let len = reader.read_u32()? as usize;
+        let values = reader.read_bytes(len * 4)?;
+        lists.push(
+            values
+                .chunks_exact(4)
+                .map(|val| u32::from_le_bytes(val.try_into().unwrap()))
+                .collect(),
+        );
+    }
+    Ok(lists)
+}
+
+//Writes lists in the binary format
+pub fn write_binary<W: Write>(lists: &[Vec<u32>], writer: &mut W) -> io::Result<()> {
+    for list in lists.iter() {
+        writer.write_all(&(list.len() as u32).to_le_bytes())?;
+        for val in list.iter() {
+            writer.write_all(&val.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn check_increasing(lists: &[Vec<u32>]) -> io::Result<()> {
+    for (index, list) in lists.iter().enumerate() {
+        if list.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("list {index} is not strictly increasing"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+
+    use super::{read_binary, read_text, run, write_binary, write_csv, Codec};
+
+    #[test]
+    fn test_read_lists() {
+        let lists = read_text(Cursor::new("1\n5\n9\n\n\n2\n 3 \n")).unwrap();
+        assert_eq!(lists, [vec![1, 5, 9], vec![2, 3]]);
+
+        let mut bytes = Vec::new();
+        write_binary(&lists, &mut bytes).unwrap();
+        assert_eq!(bytes.len(), 4 * (2 + 5));
+        assert_eq!(read_binary(&bytes).unwrap(), lists);
+
+        let err = read_text(Cursor::new("1\nx\n")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(read_text(Cursor::new("5\n1\n")).is_err());
+        assert!(read_binary(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[cfg(feature = "gb")]
+    #[test]
+    fn test_escaped_runs() {
+        let lists = vec![
+            (1..=200).collect(),
+            (1..=100).map(|val| val * 2).collect(),
+            vec![],
+        ];
+        assert_eq!(super::escaped_runs(&lists), (1, 200));
+    }
+
+    #[test]
+    fn test_run() {
+        let lists = vec![(1..=1000).map(|val| val * 3).collect(), vec![7], vec![]];
+        #[cfg(feature = "s16")]
+        assert!(!Codec::Simple16.supports(&[vec![1 << 28]]));
+
+        let mut results = Vec::new();
+        for &codec in Codec::ALL {
+            assert_eq!(Codec::from_name(codec.name()), Some(codec));
+            assert!(codec.supports(&lists));
+
+            let result = run(codec, &lists, 2);
+            assert_eq!(result.lists, 3);
+            assert_eq!(result.integers, 1001);
+            assert!(result.bits_per_integer() > 0.0);
+            results.push(result);
+        }
+
+        let mut csv = Vec::new();
+        write_csv(&results, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 1 + Codec::ALL.len());
+        for (line, result) in csv.lines().skip(1).zip(results.iter()) {
+            assert!(line.starts_with(&format!("{},3,1001,", result.codec)));
+        }
+    }
+}
@@ -0,0 +1,174 @@
+/*
+A set of u32 values, stored as one bit per value below the universe.
+
+When a result holds a large share of the universe, e.g. a disjunction of many long lists, or the
+documents counted for a facet, a bitmap is smaller and faster to fill than a compressed list.
+Values are added a word at a time: every value that falls into the same 64 bit word is collected
+into one mask, which is ORed into the bitmap once.
+*/
+
+use crate::{
+    codec::GapMode,
+    varint_gb::{DecodeContext, DescriptorTable, VarintGB},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bitmap {
+    words: Vec<u64>,
+}
+
+impl Bitmap {
+    //An empty bitmap that can hold the values below universe
+    pub fn new(universe: usize) -> Self {
+        Bitmap {
+            words: vec![0; universe.div_ceil(64)],
+        }
+    }
+
+    //The number of values the bitmap can hold, rounded up to whole words
+    pub fn universe(&self) -> usize {
+        self.words.len() * 64
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    pub fn contains(&self, value: u32) -> bool {
+        let value = value as usize;
+        value < self.universe() && self.words[value / 64] >> (value % 64) & 1 == 1
+    }
+
+    //The number of values in the bitmap
+    pub fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(|(word_index, word)| {
+                let mut word = *word;
+                std::iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+                    let bit = word.trailing_zeros();
+                    word &= word - 1;
+                    Some(word_index as u32 * 64 + bit)
+                })
+            })
+    }
+
+    //Adds the values, which must be sorted and below the universe
+    pub fn insert_sorted(&mut self, values: &[u32]) {
+        let Some(first) = values.first() else {
+            return;
+        };
+
+        let mut word_index = *first as usize / 64;
+        let mut mask = 0;
+        for val in values.iter() {
+            let val = *val as usize;
+            if val / 64 != word_index {
+                self.words[word_index] |= mask;
+                word_index = val / 64;
+                mask = 0;
+            }
+            mask |= 1 << (val % 64);
+        }
+        self.words[word_index] |= mask;
+    }
+}
+
+//Decodes every list with the SIMD decoder into one reused buffer, and sets the bits of the values.
+//The bitmap is sized to the largest value of any list, so absolute lists, whose last value need
+//not be the largest, are not allowed.
+pub fn union_into_bitmap(lists: &[VarintGB<'_>], descriptor_table: &DescriptorTable) -> Bitmap {
+    assert!(
+        lists.iter().all(|seq| seq.gap_mode() != GapMode::Absolute),
+        "absolute values are not sorted"
+    );
+    let universe = lists.iter().map(|seq| seq.top() as usize + 1).max();
+    let mut bitmap = Bitmap::new(universe.unwrap_or(0));
+
+    let mut context = DecodeContext::new(descriptor_table);
+    for seq in lists.iter() {
+        bitmap.insert_sorted(context.decode(seq));
+    }
+    bitmap
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use rand::Rng;
+
+    use crate::varint_gb::{DescriptorTable, VarintGB, VarintGBFactory};
+
+    use super::{union_into_bitmap, Bitmap};
+
+    #[test]
+    fn test_insert_sorted() {
+        let mut bitmap = Bitmap::new(200);
+        bitmap.insert_sorted(&[0, 1, 63, 64, 127, 199]);
+        bitmap.insert_sorted(&[1, 2, 128]);
+
+        assert_eq!(bitmap.universe(), 256);
+        assert_eq!(bitmap.count(), 8);
+        assert_eq!(
+            bitmap.iter().collect::<Vec<u32>>(),
+            [0, 1, 2, 63, 64, 127, 128, 199]
+        );
+        assert!(bitmap.contains(63));
+        assert!(!bitmap.contains(62));
+        assert!(!bitmap.contains(1000));
+    }
+
+    #[test]
+    fn test_union() {
+        let mut rng = rand::thread_rng();
+        let shuffle_table = DescriptorTable::new();
+
+        let mut expected = BTreeSet::new();
+        let mut lists = Vec::new();
+        for list in 0..20 {
+            //Lists of very different density, and one empty list
+            let max_gap = 1 << (list % 12);
+            let mut seq_factory = VarintGBFactory::new();
+            let mut value = 0;
+            for _ in 0..(list * 37) {
+                value += rng.gen_range(1..=max_gap);
+                seq_factory.push_int(value);
+                expected.insert(value);
+            }
+            lists.push(seq_factory.into_varint_gb());
+        }
+
+        let bitmap = union_into_bitmap(&lists, &shuffle_table);
+        assert_eq!(bitmap.count(), expected.len());
+        assert_eq!(
+            bitmap.iter().collect::<Vec<u32>>(),
+            expected.into_iter().collect::<Vec<u32>>()
+        );
+
+        let bitmap = union_into_bitmap(&[] as &[VarintGB<'_>], &shuffle_table);
+        assert_eq!(bitmap.count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "absolute values are not sorted")]
+    fn test_absolute_union() {
+        //The last value is not the largest, so the bitmap would be too small
+        let mut seq_factory = VarintGBFactory::new_absolute();
+        for val in [100, 5, 3] {
+            seq_factory.push_int(val);
+        }
+        union_into_bitmap(&[seq_factory.into_varint_gb()], &DescriptorTable::new());
+    }
+}
@@ -0,0 +1,314 @@
+/*
+A cursor over a block aligned GB sequence, that decodes a whole block at a time, and keeps the last
+few blocks it decoded.
+
+Algorithms that bounce between lists, e.g. WAND, rewind a cursor to blocks it has already passed.
+The cursor finds such a block in its cache instead of decoding it again. The cache holds
+CACHED_BLOCKS blocks, keyed by block index, and drops the least recently used one to make room.
+BlockCacheStats counts how often a block was found in the cache.
+*/
+
+use crate::{
+    codec::CursorMark,
+    varint_gb::{DescriptorTable, VarintGB},
+};
+
+//Blocks of decoded values a cursor keeps
+pub const CACHED_BLOCKS: usize = 4;
+
+//How often a block the cursor moved to was in its cache
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockCacheStats {
+    pub hits: usize,
+    //Blocks that had to be decoded
+    pub misses: usize,
+}
+
+impl BlockCacheStats {
+    //The share of blocks found in the cache, or 0 if no block was needed
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+pub struct BlockCursor<'s, 't> {
+    seq: &'s VarintGB<'s>,
+    descriptor_table: &'t DescriptorTable,
+    //Decoded blocks and their indices, most recently used first. The current block is first once
+    //it is loaded.
+    cache: Vec<(usize, Vec<u32>)>,
+    block: usize,
+    //Index of the next value in the current block
+    pos: usize,
+    stats: BlockCacheStats,
+}
+
+impl<'s, 't> BlockCursor<'s, 't> {
+    //A cursor at the start of the sequence, which must be block aligned unless it is empty
+    pub fn new(seq: &'s VarintGB<'s>, descriptor_table: &'t DescriptorTable) -> Self {
+        assert!(
+            seq.len() == 0 || !seq.blocks().is_empty(),
+            "block cursors need block headers"
+        );
+        BlockCursor {
+            seq,
+            descriptor_table,
+            cache: Vec::with_capacity(CACHED_BLOCKS),
+            block: 0,
+            pos: 0,
+            stats: BlockCacheStats::default(),
+        }
+    }
+
+    pub fn cache_stats(&self) -> BlockCacheStats {
+        self.stats
+    }
+
+    //Returns the first value >= target at or after the current position. Blocks that end below
+    //the target are skipped with the block headers, without decoding or caching them.
+    pub fn next_geq(&mut self, target: u32) -> Option<u32> {
+        let blocks = self.seq.blocks();
+        let skipped = blocks[self.block.min(blocks.len())..]
+            .partition_point(|header| header.last_value < target);
+        if skipped > 0 {
+            self.block += skipped;
+            self.pos = 0;
+        }
+        if self.block >= blocks.len() {
+            return None;
+        }
+
+        //The block ends at or past the target, so the value is in it, or starts the next block
+        let pos = self.pos;
+        let values = self.load(self.block);
+        let skipped = values[pos.min(values.len())..].partition_point(|val| *val < target);
+        self.pos += skipped;
+        self.next()
+    }
+
+    //Saves the position, so rewind can return to it. The mark holds the group of the position and
+    //the value before that group, like the marks of a ValueIter, so either can rewind to the marks
+    //of the other. Rewind itself only reads back the ordinal.
+    pub fn mark(&self) -> CursorMark {
+        let blocks = self.seq.blocks();
+        let block_len = self.seq.block_len().max(1);
+        let ordinal = (self.block * block_len + self.pos).min(self.seq.len());
+        let (block, pos) = (ordinal / block_len, ordinal % block_len);
+        let before_block = if block == 0 {
+            0
+        } else {
+            blocks[block - 1].last_value
+        };
+        let Some(header) = blocks.get(block) else {
+            return CursorMark {
+                byte_offset: self.seq.byte_stream.len(),
+                last_value: before_block,
+                ordinal: ordinal as u32,
+            };
+        };
+
+        //Blocks start on a group, so the groups before the position are walked from its start
+        let mut byte_offset = header.byte_offset as usize;
+        for _ in 0..pos / 4 {
+            let descriptor = self.seq.byte_stream[byte_offset];
+            byte_offset += 1 + self.descriptor_table.get_length_for_descriptor(descriptor) as usize;
+        }
+        let group_start = pos - pos % 4;
+        let last_value = if group_start == 0 {
+            before_block
+        } else {
+            //The block is cached unless the cursor was just rewound into it
+            match self.cache.iter().find(|(cached, _)| *cached == block) {
+                Some((_, values)) => values[group_start - 1],
+                None => self.seq.decode_block(block, self.descriptor_table)[group_start - 1],
+            }
+        };
+        CursorMark {
+            byte_offset,
+            last_value,
+            ordinal: ordinal as u32,
+        }
+    }
+
+    //Continues from a position saved by mark, before or after the current one. The block is
+    //decoded again only if it has left the cache.
+    pub fn rewind(&mut self, mark: CursorMark) {
+        let block_len = self.seq.block_len().max(1);
+        self.block = mark.ordinal as usize / block_len;
+        self.pos = mark.ordinal as usize % block_len;
+    }
+
+    //The values of the block, moved to the front of the cache
+    fn load(&mut self, block: usize) -> &[u32] {
+        match self.cache.iter().position(|(cached, _)| *cached == block) {
+            Some(0) => {}
+            Some(index) => {
+                self.stats.hits += 1;
+                self.cache[..=index].rotate_right(1);
+            }
+            None => {
+                self.stats.misses += 1;
+                //Reuse the buffer of the block that drops out
+                let mut values = if self.cache.len() == CACHED_BLOCKS {
+                    self.cache.pop().unwrap().1
+                } else {
+                    Vec::with_capacity(self.seq.block_len() + 4)
+                };
+                values.clear();
+                self.seq
+                    .decode_block_into(block, self.descriptor_table, &mut values);
+                self.cache.insert(0, (block, values));
+            }
+        }
+        &self.cache[0].1
+    }
+}
+
+impl Iterator for BlockCursor<'_, '_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.block < self.seq.blocks().len() {
+            let pos = self.pos;
+            if let Some(val) = self.load(self.block).get(pos).copied() {
+                self.pos += 1;
+                return Some(val);
+            }
+            self.block += 1;
+            self.pos = 0;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        set_ops::intersect_cursors_with_stats,
+        varint_gb::{DescriptorTable, VarintGBFactory},
+    };
+
+    use super::{BlockCacheStats, BlockCursor, CACHED_BLOCKS};
+
+    #[test]
+    fn test_block_cursor() {
+        let shuffle_table = DescriptorTable::new();
+        let reference_vector: Vec<u32> = (1..=1001).map(|val| val * 7).collect();
+        let mut seq_factory = VarintGBFactory::new_with_block_len(100);
+        for val in reference_vector.iter() {
+            seq_factory.push_int(*val);
+        }
+        let seq = seq_factory.into_varint_gb();
+
+        let cursor = BlockCursor::new(&seq, &shuffle_table);
+        assert_eq!(cursor.collect::<Vec<u32>>(), reference_vector);
+
+        let mut cursor = BlockCursor::new(&seq, &shuffle_table);
+        let mut position = 0;
+        for target in [0, 7, 8, 700, 701, 3000, 3000, 7007, 7008] {
+            let skipped = reference_vector[position..]
+                .iter()
+                .position(|val| *val >= target);
+            let expected = skipped.map(|skipped| reference_vector[position + skipped]);
+            position = skipped.map_or(reference_vector.len(), |skipped| position + skipped + 1);
+            assert_eq!(cursor.next_geq(target), expected);
+            assert_eq!(cursor.mark().ordinal as usize, position);
+        }
+        assert_eq!(cursor.next(), None);
+
+        //Blocks skipped by next_geq are never decoded
+        let mut cursor = BlockCursor::new(&seq, &shuffle_table);
+        assert_eq!(cursor.next_geq(7 * 950), Some(7 * 950));
+        assert_eq!(cursor.cache_stats(), BlockCacheStats { hits: 0, misses: 1 });
+        assert_eq!(cursor.next_geq(7 * 1002), None);
+    }
+
+    #[test]
+    fn test_block_cache() {
+        let shuffle_table = DescriptorTable::new();
+        let reference_vector: Vec<u32> = (1..=1000).map(|val| val * 3).collect();
+        let mut seq_factory = VarintGBFactory::new_with_block_len(100);
+        for val in reference_vector.iter() {
+            seq_factory.push_int(*val);
+        }
+        let seq = seq_factory.into_varint_gb();
+
+        //Marks at the start of every block, and in the middle of block 2
+        let mut cursor = BlockCursor::new(&seq, &shuffle_table);
+        let mut marks = Vec::new();
+        for ordinal in 0..reference_vector.len() {
+            if ordinal % 100 == 0 {
+                marks.push(cursor.mark());
+            }
+            if ordinal == 250 {
+                marks.push(cursor.mark());
+            }
+            cursor.next();
+        }
+        assert_eq!(marks[2].byte_offset, seq.blocks()[2].byte_offset as usize);
+        assert_eq!(marks[2].last_value, 3 * 200);
+        assert_eq!(cursor.cache_stats().misses, 10);
+        assert_eq!(cursor.cache_stats().hits, 0);
+
+        //Blocks 6 to 9 are cached, and each miss drops the least recently used one
+        for (mark, hit) in [(3, false), (8, true), (0, false), (10, true), (4, false)] {
+            let before = cursor.cache_stats();
+            cursor.rewind(marks[mark]);
+            let ordinal = marks[mark].ordinal as usize;
+            let rest: Vec<u32> = cursor.by_ref().take(3).collect();
+            assert_eq!(rest, reference_vector[ordinal..ordinal + 3]);
+            let after = cursor.cache_stats();
+            assert_eq!(after.hits - before.hits, hit as usize);
+            assert_eq!(after.misses - before.misses, !hit as usize);
+        }
+        assert_eq!(CACHED_BLOCKS, 4);
+        assert!((cursor.cache_stats().hit_rate() - 2.0 / 15.0).abs() < 1e-9);
+        assert_eq!(BlockCacheStats::default().hit_rate(), 0.0);
+
+        //Marks of a value iterator rewind a block cursor too, and the other way around, also from
+        //the middle of a block
+        let mut iter = seq.iter_values(&shuffle_table);
+        iter.nth(444);
+        cursor.rewind(iter.mark());
+        assert_eq!(cursor.next(), Some(3 * 446));
+        let mut iter = seq.iter_values(&shuffle_table);
+        iter.rewind(marks[3]);
+        assert_eq!(iter.take(3).collect::<Vec<u32>>(), [753, 756, 759]);
+        for mark in marks.iter() {
+            let mut iter = seq.iter_values(&shuffle_table);
+            iter.rewind(*mark);
+            let ordinal = mark.ordinal as usize;
+            assert!(iter.eq(reference_vector[ordinal..].iter().copied()));
+        }
+
+        //Marks taken right after a rewind, before the block is decoded again
+        cursor.rewind(marks[3]);
+        assert_eq!(cursor.mark(), marks[3]);
+        cursor.rewind(marks[10]);
+        assert_eq!(cursor.mark(), marks[10]);
+
+        //Intersections can run on block cursors
+        let mut other_factory = VarintGBFactory::new();
+        for val in (0..2000).map(|val| val * 5) {
+            other_factory.push_int(val);
+        }
+        let other = other_factory.into_varint_gb();
+        let (result, _) = intersect_cursors_with_stats(
+            (BlockCursor::new(&seq, &shuffle_table), seq.len()),
+            (other.iter_values(&shuffle_table), other.len()),
+            VarintGBFactory::new(),
+        );
+        let expected: Vec<u32> = (1..=200).map(|val| val * 15).collect();
+        assert_eq!(result.get_values(&shuffle_table), expected);
+
+        let empty = VarintGBFactory::new_block_aligned().into_varint_gb();
+        let mut cursor = BlockCursor::new(&empty, &shuffle_table);
+        assert_eq!(cursor.next_geq(0), None);
+        assert_eq!(cursor.next(), None);
+    }
+}
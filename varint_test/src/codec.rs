@@ -0,0 +1,269 @@
+/*
+The interface shared by the compressed integer sequences, so the benchmarks and the index code can
+be written once and run with any codec.
+
+A sequence is built by pushing its values in increasing order into a factory, and finishing it.
+The finished sequence knows its length and size, and iterates its values as u32.
+*/
+
+#[cfg(feature = "ef")]
+use crate::elias_fano::{EliasFano, EliasFanoFactory};
+#[cfg(feature = "s16")]
+use crate::simple16::{Simple16, Simple16Factory};
+#[cfg(feature = "gb")]
+use crate::varint_gb::{DescriptorTable, VarintGB, VarintGBFactory};
+#[cfg(feature = "su")]
+use crate::varint_su::{VarintSU, VarintSUFactory};
+
+pub trait IntSequenceFactory {
+    type Output: IntSequenceCodec;
+
+    //Values must be pushed in increasing order, unless the factory stores absolute values
+    fn push(&mut self, value: u32);
+
+    fn finish(self) -> Self::Output;
+}
+
+pub trait IntSequenceCodec {
+    type Iter<'s>: Iterator<Item = u32>
+    where
+        Self: 's;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    //Bytes of encoded values, not counting the struct itself or any index next to the values
+    fn size_in_bytes(&self) -> usize;
+
+    //Iterates exactly len values, from the start
+    fn values(&self) -> Self::Iter<'_>;
+}
+
+//How the values of a sequence are stored. Strict sequences are strictly increasing, so every gap is
+//at least 1, and gap - 1 is stored. NonStrict sequences may repeat values, so the gap itself is
+//stored. Absolute sequences may come in any order, e.g. term frequencies, so the values themselves
+//are stored. Seeking with next_geq, block headers and restart points assume sorted values.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GapMode {
+    #[default]
+    Strict,
+    NonStrict,
+    Absolute,
+}
+
+impl GapMode {
+    //The byte the mode is serialized as
+    pub fn to_u8(self) -> u8 {
+        match self {
+            GapMode::Strict => 0,
+            GapMode::NonStrict => 1,
+            GapMode::Absolute => 2,
+        }
+    }
+
+    pub fn from_u8(mode: u8) -> Option<Self> {
+        match mode {
+            0 => Some(GapMode::Strict),
+            1 => Some(GapMode::NonStrict),
+            2 => Some(GapMode::Absolute),
+            _ => None,
+        }
+    }
+
+    //What is subtracted from a gap before it is written
+    #[cfg(feature = "su")]
+    pub(crate) fn adjustment(self) -> u32 {
+        match self {
+            GapMode::Strict => 1,
+            GapMode::NonStrict | GapMode::Absolute => 0,
+        }
+    }
+}
+
+//A saved position of a cursor, to rewind it to later. Holds the byte offset decoding continues
+//from, the value gaps are added to there, and the index of the next value. A mark is only valid
+//for cursors over the sequence it was taken from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CursorMark {
+    pub byte_offset: usize,
+    pub last_value: u32,
+    pub ordinal: u32,
+}
+
+//Pushes every value into the factory, and finishes the sequence
+pub fn encode<F: IntSequenceFactory>(mut factory: F, values: &[u32]) -> F::Output {
+    for val in values.iter() {
+        factory.push(*val);
+    }
+    factory.finish()
+}
+
+#[cfg(feature = "gb")]
+impl IntSequenceFactory for VarintGBFactory {
+    type Output = VarintGB<'static>;
+
+    fn push(&mut self, value: u32) {
+        self.push_int(value);
+    }
+
+    fn finish(self) -> Self::Output {
+        self.into_varint_gb()
+    }
+}
+
+//Decodes with the process-wide descriptor table, as the trait has no place to pass one
+#[cfg(feature = "gb")]
+impl IntSequenceCodec for VarintGB<'_> {
+    type Iter<'s>
+        = crate::varint_gb::ValueIter<'s, 'static>
+    where
+        Self: 's;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        self.byte_stream.len()
+    }
+
+    fn values(&self) -> Self::Iter<'_> {
+        self.iter_values(DescriptorTable::global())
+    }
+}
+
+#[cfg(feature = "su")]
+impl IntSequenceFactory for VarintSUFactory {
+    type Output = VarintSU<'static>;
+
+    fn push(&mut self, value: u32) {
+        self.push_int(value);
+    }
+
+    fn finish(self) -> Self::Output {
+        self.into_varint_su()
+    }
+}
+
+#[cfg(feature = "su")]
+impl IntSequenceCodec for VarintSU<'_> {
+    type Iter<'s>
+        = std::iter::Map<crate::varint_su::Iter<'s>, fn(usize) -> u32>
+    where
+        Self: 's;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn values(&self) -> Self::Iter<'_> {
+        self.iter().map(|val| val as u32)
+    }
+}
+
+#[cfg(feature = "s16")]
+impl IntSequenceFactory for Simple16Factory {
+    type Output = Simple16;
+
+    fn push(&mut self, value: u32) {
+        self.push_int(value);
+    }
+
+    fn finish(self) -> Self::Output {
+        self.into_simple16()
+    }
+}
+
+#[cfg(feature = "s16")]
+impl IntSequenceCodec for Simple16 {
+    type Iter<'s> = crate::simple16::Iter<'s>;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes()
+    }
+
+    fn values(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+}
+
+#[cfg(feature = "ef")]
+impl IntSequenceFactory for EliasFanoFactory {
+    type Output = EliasFano;
+
+    fn push(&mut self, value: u32) {
+        self.push_int(value);
+    }
+
+    fn finish(self) -> Self::Output {
+        self.into_elias_fano()
+    }
+}
+
+#[cfg(feature = "ef")]
+impl IntSequenceCodec for EliasFano {
+    type Iter<'s> = crate::elias_fano::Iter<'s>;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes()
+    }
+
+    fn values(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+}
+
+#[cfg(all(test, feature = "gb", feature = "su", feature = "s16", feature = "ef"))]
+mod tests {
+    use rand::Rng;
+
+    use crate::{
+        elias_fano::EliasFanoFactory, simple16::Simple16Factory, varint_gb::VarintGBFactory,
+        varint_su::VarintSUFactory,
+    };
+
+    use super::{encode, IntSequenceCodec, IntSequenceFactory};
+
+    fn check_codec<F: IntSequenceFactory>(make_factory: impl Fn() -> F, reference_vector: &[u32]) {
+        let seq = encode(make_factory(), reference_vector);
+        assert_eq!(seq.len(), reference_vector.len());
+        assert_eq!(seq.is_empty(), reference_vector.is_empty());
+        assert_eq!(seq.values().collect::<Vec<u32>>(), reference_vector);
+    }
+
+    #[test]
+    fn test_codecs_agree() {
+        let mut rng = rand::thread_rng();
+
+        //Lengths around the GB group size, so the final group is padded in every way
+        for len in [0, 1, 2, 3, 4, 5, 129, 1000] {
+            let mut reference_vector = Vec::new();
+            let mut value = 0;
+            for _ in 0..len {
+                value += rng.gen_range(1..1 << 20);
+                reference_vector.push(value);
+            }
+
+            check_codec(VarintGBFactory::new, &reference_vector);
+            check_codec(VarintGBFactory::new_block_aligned, &reference_vector);
+            check_codec(VarintSUFactory::new, &reference_vector);
+            check_codec(Simple16Factory::new, &reference_vector);
+            check_codec(EliasFanoFactory::new, &reference_vector);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+/*
+Generators for sorted sequences that are worst cases for one of the codecs, and for sequences that
+resemble real lists.
+
+Values are u32, so long gaps can only be repeated so many times before the sequence overflows.
+Every generator panics if the requested length would not fit.
+
+A GapProfile is fitted to real lists by fit_gap_profile. Gaps of 1 come in runs, whose starts and
+lengths are counted. The other gaps are fitted with a power law from 2 up to the largest gap, by
+the maximum likelihood estimate of the exponent, with the usual correction of half a step for
+discrete values. calibrated_list draws lists from a profile, so benchmarks can run on synthetic
+lists with the gaps of an actual index.
+*/
+
+use rand::Rng;
+
+//Gaps alternate between 1 byte and 3 bytes, so every GB group mixes widths and the byte stream
+//cannot be split into runs of identical descriptors
+pub fn gb_alternating_widths(len: usize) -> Vec<u32> {
+    let gaps = [1, 1 << 16];
+    from_gaps(len, |i| gaps[i % 2])
+}
+
+//Every gap takes 4 bytes in GB, which is the most bytes per value the format allows
+pub fn gb_max_width(len: usize) -> Vec<u32> {
+    from_gaps(len, |_| 1 << 24)
+}
+
+//Every gap is the smallest that needs `bytes` bytes in strict SU, so every value walks a
+//continuation chain of bytes - 1 bytes
+pub fn su_continuation_chain(len: usize, bytes: u32) -> Vec<u32> {
+    let gap = su_chain_gap(bytes);
+    from_gaps(len, |_| gap)
+}
+
+//The longest continuation chain sequence of the byte width that fits in u32
+pub fn su_continuation_chain_max_len(bytes: u32) -> usize {
+    (u32::MAX / su_chain_gap(bytes)) as usize
+}
+
+fn su_chain_gap(bytes: u32) -> u32 {
+    assert!((1..=5).contains(&bytes));
+
+    //A stored number needs k bytes from 128 + 128^2 + ... + 128^(k - 1), and the stored number
+    //is the gap - 1
+    let mut min_stored: u64 = 0;
+    for i in 1..bytes {
+        min_stored += 128u64.pow(i);
+    }
+    u32::try_from(min_stored + 1).unwrap()
+}
+
+//Runs of run_len consecutive values, separated by jumps of jump, as in clustered data
+pub fn dense_runs(len: usize, run_len: usize, jump: u32) -> Vec<u32> {
+    assert!(run_len > 0);
+    from_gaps(len, |i| if i % run_len == 0 { jump } else { 1 })
+}
+
+//The adversarial inputs the main benchmark times, by name
+pub fn benchmark_inputs() -> Vec<(&'static str, Vec<u32>)> {
+    vec![
+        ("GB alternating widths", gb_alternating_widths(100000)),
+        ("GB max width", gb_max_width(255)),
+        (
+            "SU 4 byte chains",
+            su_continuation_chain(su_continuation_chain_max_len(4), 4),
+        ),
+        ("Dense runs", dense_runs(100000, 1000, 1 << 20)),
+    ]
+}
+
+//Gap statistics of a set of lists
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GapProfile {
+    pub gaps: usize,
+    //Exponent of the power law of the gaps of 2 or more, or 0 if there are none
+    pub exponent: f64,
+    pub max_gap: u32,
+    //Runs of gaps of 1, the chance that a run follows another gap, and the run lengths
+    pub runs: usize,
+    pub run_start: f64,
+    pub mean_run_len: f64,
+    pub longest_run: usize,
+}
+
+//The gap profile of the lists. The first value of every list is a gap from 0, and runs do not
+//continue from one list into the next. Gaps of 0 are counted, but fitted as neither.
+pub fn fit_gap_profile(lists: &[Vec<u32>]) -> GapProfile {
+    let mut gaps = 0;
+    let mut other_gaps = 0;
+    let mut power_law_gaps = 0;
+    let mut log_sum = 0.0;
+    let mut max_gap = 0;
+    let mut runs = 0;
+    let mut gaps_in_runs = 0;
+    let mut longest_run = 0;
+
+    for list in lists.iter() {
+        let mut top = 0;
+        let mut run = 0;
+        for val in list.iter() {
+            let gap = val - top;
+            top = *val;
+            gaps += 1;
+
+            if gap == 1 {
+                if run == 0 {
+                    runs += 1;
+                }
+                run += 1;
+                gaps_in_runs += 1;
+                longest_run = longest_run.max(run);
+                continue;
+            }
+            run = 0;
+            other_gaps += 1;
+            if gap >= 2 {
+                power_law_gaps += 1;
+                log_sum += (gap as f64 / 1.5).ln();
+                max_gap = max_gap.max(gap);
+            }
+        }
+    }
+
+    GapProfile {
+        gaps,
+        exponent: if power_law_gaps > 0 {
+            1.0 + power_law_gaps as f64 / log_sum
+        } else {
+            0.0
+        },
+        max_gap,
+        runs,
+        run_start: (runs as f64 / other_gaps.max(1) as f64).min(1.0),
+        mean_run_len: if runs > 0 {
+            gaps_in_runs as f64 / runs as f64
+        } else {
+            0.0
+        },
+        longest_run,
+    }
+}
+
+//A list of len values whose gaps are drawn from the profile. After any other gap, a run of gaps of
+//1 starts with chance run_start, and its length is geometric with mean mean_run_len. The other
+//gaps follow the power law, cut off at max_gap, and are 2 if the profile has none.
+pub fn calibrated_list<R: Rng>(profile: &GapProfile, len: usize, rng: &mut R) -> Vec<u32> {
+    let run_end = if profile.mean_run_len > 0.0 {
+        (1.0 / profile.mean_run_len).min(1.0)
+    } else {
+        1.0
+    };
+
+    let mut in_run = false;
+    let gaps: Vec<u32> = (0..len)
+        .map(|_| {
+            in_run = if in_run {
+                !rng.gen_bool(run_end)
+            } else {
+                rng.gen_bool(profile.run_start)
+            };
+            if in_run {
+                1
+            } else {
+                power_law_gap(profile.exponent, profile.max_gap, rng)
+            }
+        })
+        .collect();
+    from_gaps(len, |i| gaps[i])
+}
+
+//A gap from the power law with the exponent, over the continuous range from 1.5 to max_gap + 0.5,
+//rounded to the nearest integer. Drawn by inverting the distribution function.
+fn power_law_gap<R: Rng>(exponent: f64, max_gap: u32, rng: &mut R) -> u32 {
+    if exponent <= 1.0 || max_gap <= 2 {
+        return 2;
+    }
+    let min = 1.5;
+    let max = max_gap as f64 + 0.5;
+    let tail = (max / min).powf(1.0 - exponent);
+    let u: f64 = rng.gen();
+    let gap = min * (1.0 - u * (1.0 - tail)).powf(-1.0 / (exponent - 1.0));
+    (gap.round() as u32).clamp(2, max_gap)
+}
+
+fn from_gaps<F: Fn(usize) -> u32>(len: usize, gap: F) -> Vec<u32> {
+    let mut values = Vec::with_capacity(len);
+    let mut value: u32 = 0;
+    for i in 0..len {
+        value = value
+            .checked_add(gap(i))
+            .expect("sequence does not fit in u32");
+        values.push(value);
+    }
+    values
+}
+
+#[cfg(all(test, feature = "gb", feature = "su", feature = "s16"))]
+mod tests {
+    use crate::{
+        simple16::Simple16Factory,
+        varint_gb::{DescriptorTable, VarintGBFactory},
+        varint_su::VarintSUFactory,
+    };
+
+    use super::{
+        benchmark_inputs, calibrated_list, dense_runs, fit_gap_profile, gb_alternating_widths,
+        gb_max_width, su_continuation_chain, su_continuation_chain_max_len, GapProfile,
+    };
+
+    fn adversarial_sequences() -> Vec<Vec<u32>> {
+        vec![
+            gb_alternating_widths(10001),
+            gb_max_width(255),
+            su_continuation_chain(1000, 4),
+            su_continuation_chain(15, 5),
+            dense_runs(10000, 300, 1 << 20),
+        ]
+    }
+
+    #[test]
+    fn test_widths() {
+        let seq = gb_alternating_widths(8);
+        let mut fact = VarintGBFactory::new();
+        for val in seq.iter() {
+            fact.push_int(*val);
+        }
+        assert_eq!(
+            fact.into_varint_gb().stats().bytes_per_length,
+            [4, 0, 12, 0]
+        );
+
+        for bytes in 1..=5 {
+            let seq = su_continuation_chain(3, bytes);
+            let mut fact = VarintSUFactory::new();
+            for val in seq.iter() {
+                fact.push_int(*val);
+            }
+            assert_eq!(fact.into_varint_su().bytes.len(), 3 * bytes as usize);
+        }
+    }
+
+    #[test]
+    fn test_benchmark_inputs() {
+        //Every input the main benchmark times fits in u32
+        for (name, input) in benchmark_inputs() {
+            assert!(!input.is_empty(), "{name}");
+            assert!(input.windows(2).all(|w| w[0] < w[1]), "{name}");
+        }
+
+        //Shorter chains fit hundreds of millions of values
+        for bytes in 3..=5 {
+            let len = su_continuation_chain_max_len(bytes);
+            let chain = su_continuation_chain(len, bytes);
+            assert!(chain[len - 1].checked_add(chain[0]).is_none());
+        }
+        assert_eq!(su_continuation_chain_max_len(4), 2032);
+    }
+
+    #[test]
+    fn test_gap_profile() {
+        let profile = fit_gap_profile(&[dense_runs(10000, 100, 1000), vec![]]);
+        assert_eq!(profile.gaps, 10000);
+        assert_eq!(profile.max_gap, 1000);
+        assert_eq!(profile.runs, 100);
+        assert_eq!(profile.run_start, 1.0);
+        assert_eq!(profile.mean_run_len, 99.0);
+        assert_eq!(profile.longest_run, 99);
+        assert!((profile.exponent - (1.0 + 1.0 / (1000.0f64 / 1.5).ln())).abs() < 1e-9);
+
+        //Runs end with their list
+        let profile = fit_gap_profile(&[vec![1, 2, 3], vec![1, 2], vec![0, 0, 5]]);
+        assert_eq!(profile.runs, 2);
+        assert_eq!(profile.longest_run, 3);
+        assert_eq!(profile.max_gap, 5);
+
+        //Lists drawn from a profile are fitted to about the same profile
+        let mut rng = rand::thread_rng();
+        let profile = GapProfile {
+            gaps: 0,
+            exponent: 2.2,
+            max_gap: 1 << 16,
+            runs: 0,
+            run_start: 0.3,
+            mean_run_len: 8.0,
+            longest_run: 0,
+        };
+        let lists: Vec<Vec<u32>> = (0..20)
+            .map(|_| calibrated_list(&profile, 10000, &mut rng))
+            .collect();
+        assert!(lists
+            .iter()
+            .all(|list| list.windows(2).all(|w| w[0] < w[1])));
+        let fitted = fit_gap_profile(&lists);
+        assert_eq!(fitted.gaps, 200000);
+        assert!(fitted.max_gap <= 1 << 16);
+        assert!((fitted.exponent - 2.2).abs() < 0.1, "{fitted:?}");
+        assert!((fitted.run_start - 0.3).abs() < 0.02, "{fitted:?}");
+        assert!((fitted.mean_run_len - 8.0).abs() < 0.5, "{fitted:?}");
+
+        //Without runs or other gaps to draw from
+        let flat = fit_gap_profile(&[vec![2, 4, 6]]);
+        assert_eq!(calibrated_list(&flat, 3, &mut rng), [2, 4, 6]);
+        assert_eq!(calibrated_list(&fit_gap_profile(&[]), 2, &mut rng), [2, 4]);
+    }
+
+    #[test]
+    fn test_roundtrips() {
+        let shuffle_table = DescriptorTable::new();
+
+        for reference_vector in adversarial_sequences() {
+            let mut gb_fact = VarintGBFactory::new();
+            let mut su_fact = VarintSUFactory::new();
+            for val in reference_vector.iter() {
+                gb_fact.push_int(*val);
+                su_fact.push_int(*val);
+            }
+
+            let gb = gb_fact.into_varint_gb();
+            assert_eq!(gb.validate(), Ok(()));
+            assert_eq!(gb.get_values_run_aware(&shuffle_table), reference_vector);
+            let values: Vec<u32> = gb.iter_values(&shuffle_table).collect();
+            assert_eq!(values, reference_vector);
+
+            let su = su_fact.into_varint_su();
+            let values: Vec<u32> = su.iter().map(|val| val as u32).collect();
+            assert_eq!(values, reference_vector);
+
+            //Simple-16 only takes gaps below 2^28
+            if reference_vector.windows(2).all(|w| w[1] - w[0] < 1 << 28) {
+                let mut s16_fact = Simple16Factory::new();
+                for val in reference_vector.iter() {
+                    s16_fact.push_int(*val);
+                }
+                let values: Vec<u32> = s16_fact.into_simple16().iter().collect();
+                assert_eq!(values, reference_vector);
+            }
+        }
+    }
+}
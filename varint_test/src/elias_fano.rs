@@ -0,0 +1,250 @@
+/*
+This refers to an implementation of a compressed integer sequence in the Elias-Fano representation
+
+Every value is split into its lowest low_bits bits, and the bits above them. The low bits of all
+values are packed one after another. The high bits are written in unary into a bit vector, where
+value i sets bit (value >> low_bits) + i:
+
+[Low bits of every value] [Bit vector of high bits]
+
+low_bits is floor(log2(universe / len)), which needs at most 2 + log2(universe / len) bits per
+value, however the gaps are spread. This suits doc id lists, whose universe is the number of
+documents. Values must be pushed in non-decreasing order, and may repeat.
+*/
+
+pub struct EliasFano {
+    low: Box<[u64]>,
+    high: Box<[u64]>,
+    low_bits: u32,
+    len: u32,
+}
+
+impl EliasFano {
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        (self.low.len() + self.high.len()) * 8
+    }
+
+    pub fn low_bits(&self) -> u32 {
+        self.low_bits
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            low: &self.low,
+            high: &self.high,
+            low_bits: self.low_bits,
+            word_index: 0,
+            word: self.high.first().copied().unwrap_or(0),
+            index: 0,
+            len: self.len,
+        }
+    }
+}
+
+pub struct Iter<'a> {
+    low: &'a [u64],
+    high: &'a [u64],
+    low_bits: u32,
+    //The word of the bit vector being scanned, with the bits already visited cleared
+    word_index: usize,
+    word: u64,
+    index: u32,
+    len: u32,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index == self.len {
+            return None;
+        }
+
+        while self.word == 0 {
+            self.word_index += 1;
+            self.word = self.high[self.word_index];
+        }
+        let bit = self.word.trailing_zeros() as usize;
+        self.word &= self.word - 1;
+
+        let high = (self.word_index * 64 + bit) as u64 - self.index as u64;
+        let low = read_bits(self.low, self.index as usize, self.low_bits);
+        self.index += 1;
+        Some((high << self.low_bits | low) as u32)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.len - self.index) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+//Reads the index'th field of width bits from the packed words
+fn read_bits(words: &[u64], index: usize, bits: u32) -> u64 {
+    if bits == 0 {
+        return 0;
+    }
+
+    let pos = index * bits as usize;
+    let shift = pos % 64;
+    let mut field = words[pos / 64] >> shift;
+    if shift + bits as usize > 64 {
+        field |= words[pos / 64 + 1] << (64 - shift);
+    }
+    field & ((1 << bits) - 1)
+}
+
+//Writes the index'th field of width bits into the packed words, which must be 0 there
+fn write_bits(words: &mut [u64], index: usize, bits: u32, field: u64) {
+    if bits == 0 {
+        return;
+    }
+
+    let pos = index * bits as usize;
+    let shift = pos % 64;
+    words[pos / 64] |= field << shift;
+    if shift + bits as usize > 64 {
+        words[pos / 64 + 1] |= field >> (64 - shift);
+    }
+}
+
+//Picks floor(log2(universe / len)), or 0 if there are more values than the universe holds
+fn pick_low_bits(len: usize, top: u32) -> u32 {
+    if len == 0 {
+        return 0;
+    }
+    let ratio = (top as u64 + 1) / len as u64;
+    ratio.checked_ilog2().unwrap_or(0)
+}
+
+//The number of low bits depends on the length and the largest value, so the values are kept
+//until the sequence is finished
+pub struct EliasFanoFactory {
+    values: Vec<u32>,
+}
+
+impl EliasFanoFactory {
+    pub fn new() -> Self {
+        EliasFanoFactory { values: Vec::new() }
+    }
+
+    pub fn push_int(&mut self, x: u32) {
+        assert!(
+            self.values.last().is_none_or(|top| *top <= x),
+            "values must be pushed in increasing order"
+        );
+        self.values.push(x);
+    }
+
+    pub fn into_elias_fano(self) -> EliasFano {
+        let len = self.values.len();
+        let top = self.values.last().copied().unwrap_or(0);
+        let low_bits = pick_low_bits(len, top);
+
+        let mut low = vec![0; (len * low_bits as usize).div_ceil(64)];
+        let high_len = len + (top as u64 >> low_bits) as usize + 1;
+        let mut high = vec![0; high_len.div_ceil(64)];
+
+        for (i, val) in self.values.iter().enumerate() {
+            write_bits(&mut low, i, low_bits, *val as u64 & ((1 << low_bits) - 1));
+            let pos = (*val as u64 >> low_bits) as usize + i;
+            high[pos / 64] |= 1 << (pos % 64);
+        }
+
+        EliasFano {
+            low: low.into_boxed_slice(),
+            high: high.into_boxed_slice(),
+            low_bits,
+            len: len as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::{pick_low_bits, read_bits, write_bits, EliasFanoFactory};
+
+    #[test]
+    fn test_bits() {
+        //Fields of 7 bits cross the word boundary at the tenth field
+        let mut words = [0; 2];
+        for i in 0..18 {
+            write_bits(&mut words, i, 7, (i as u64 * 37) & 127);
+        }
+        for i in 0..18 {
+            assert_eq!(read_bits(&words, i, 7), (i as u64 * 37) & 127);
+        }
+    }
+
+    #[test]
+    fn test_low_bits() {
+        assert_eq!(pick_low_bits(0, 0), 0);
+        assert_eq!(pick_low_bits(10, 5), 0);
+        assert_eq!(pick_low_bits(1, u32::MAX), 32);
+        assert_eq!(pick_low_bits(100, 1599), 4);
+        assert_eq!(pick_low_bits(100, 1600), 4);
+        assert_eq!(pick_low_bits(100, 3199), 5);
+    }
+
+    #[test]
+    fn test_compressions() {
+        let mut rng = rand::thread_rng();
+
+        for len in [0, 1, 3, 64, 65, 1000] {
+            let mut reference_vector = Vec::new();
+            let mut value = 0;
+            for _ in 0..len {
+                //Repeats, small gaps and the occasional large one
+                value += match rng.gen_range(0..10) {
+                    0 => 0,
+                    1 => rng.gen_range(1..1 << 24),
+                    _ => rng.gen_range(1..100),
+                };
+                reference_vector.push(value);
+            }
+
+            let mut fact = EliasFanoFactory::new();
+            for val in reference_vector.iter() {
+                fact.push_int(*val);
+            }
+            let seq = fact.into_elias_fano();
+
+            assert_eq!(seq.len(), len);
+            assert_eq!(seq.iter().len(), len);
+            assert_eq!(seq.iter().collect::<Vec<u32>>(), reference_vector);
+        }
+
+        for reference_vector in [vec![0], vec![u32::MAX], vec![0, 0, 0], vec![0, u32::MAX]] {
+            let mut fact = EliasFanoFactory::new();
+            for val in reference_vector.iter() {
+                fact.push_int(*val);
+            }
+            let seq = fact.into_elias_fano();
+            assert_eq!(seq.iter().collect::<Vec<u32>>(), reference_vector);
+        }
+    }
+
+    #[test]
+    fn test_size() {
+        //Every value costs at most 2 + log2(universe / len) bits, plus the final bit and the
+        //rounding of both parts to whole words
+        let reference_vector: Vec<u32> = (1..=10000).map(|val| val * 1000).collect();
+        let mut fact = EliasFanoFactory::new();
+        for val in reference_vector.iter() {
+            fact.push_int(*val);
+        }
+        let seq = fact.into_elias_fano();
+
+        assert_eq!(seq.low_bits(), 9);
+        let max_bits = 10000.0 * (2.0 + (10000001.0_f64 / 10000.0).log2()) + 1.0;
+        assert!(seq.size_in_bytes() as f64 <= max_bits / 8.0 + 16.0);
+    }
+}
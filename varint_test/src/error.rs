@@ -0,0 +1,85 @@
+use std::fmt;
+
+use crate::frame::FrameError;
+
+//Errors found when checking that an encoded sequence is consistent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodecError {
+    //The descriptor at `offset` declares more bytes than are left in the stream
+    TruncatedGroup { offset: usize },
+    //The stream continues at `offset` after the last value
+    TrailingBytes { offset: usize },
+    //The stream holds a different number of values than the stored length
+    LengthMismatch { expected: usize, found: usize },
+    //The block header does not match the byte stream
+    InvalidBlockHeader { block: usize },
+    //The restart point does not start a value, or does not match the value there
+    InvalidRestart { restart: usize },
+    //Serialized bytes were written in a format version this build cannot read
+    UnsupportedVersion { version: u8 },
+    //Serialized bytes name a gap mode that does not exist
+    InvalidGapMode { mode: u8 },
+    //Serialized bytes set flags that do not exist
+    InvalidFlags { flags: u8 },
+    //The escaped run at `offset` does not continue the values before it by whole groups
+    InvalidEscape { offset: usize },
+    //The group at `offset` takes the values past u32::MAX
+    ValueOverflow { offset: usize },
+    //A serialized field the decoders rely on does not match the values in the stream
+    HeaderMismatch { field: &'static str },
+    //The serialized descriptor table entry for `descriptor` is not the one the format defines
+    InvalidDescriptorEntry { descriptor: u8 },
+    //Serialized bytes are cut off, or continue past the end of the sequence
+    Frame(FrameError),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::TruncatedGroup { offset } => {
+                write!(f, "group at byte {offset} is truncated")
+            }
+            CodecError::TrailingBytes { offset } => {
+                write!(f, "trailing bytes after the last value, at byte {offset}")
+            }
+            CodecError::LengthMismatch { expected, found } => {
+                write!(f, "expected {expected} values, found {found}")
+            }
+            CodecError::InvalidBlockHeader { block } => {
+                write!(f, "header of block {block} does not match the byte stream")
+            }
+            CodecError::InvalidRestart { restart } => {
+                write!(f, "restart point {restart} does not match the byte stream")
+            }
+            CodecError::UnsupportedVersion { version } => {
+                write!(f, "unsupported format version {version}")
+            }
+            CodecError::InvalidGapMode { mode } => write!(f, "invalid gap mode {mode}"),
+            CodecError::InvalidFlags { flags } => write!(f, "invalid flags {flags:#04x}"),
+            CodecError::InvalidEscape { offset } => {
+                write!(
+                    f,
+                    "escaped run at byte {offset} does not fit the values before it"
+                )
+            }
+            CodecError::ValueOverflow { offset } => {
+                write!(f, "group at byte {offset} overflows u32")
+            }
+            CodecError::HeaderMismatch { field } => {
+                write!(f, "header field {field} does not match the byte stream")
+            }
+            CodecError::InvalidDescriptorEntry { descriptor } => {
+                write!(f, "invalid descriptor table entry for {descriptor:#010b}")
+            }
+            CodecError::Frame(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<FrameError> for CodecError {
+    fn from(err: FrameError) -> Self {
+        CodecError::Frame(err)
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+/*
+A reader for data we did not write ourselves, e.g. bytes loaded from disk.
+
+Every read is bounds checked against the remaining bytes, and fails with a FrameError
+carrying the offset of the read, instead of panicking on out of range slicing.
+Frames are length-prefixed: [Length (u32, little endian)] [Length bytes]
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    //A read of `needed` bytes at `offset`, with only `available` bytes left
+    UnexpectedEnd {
+        offset: usize,
+        needed: usize,
+        available: usize,
+    },
+    //Bytes were left over after the last expected read
+    TrailingBytes {
+        offset: usize,
+        remaining: usize,
+    },
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::UnexpectedEnd {
+                offset,
+                needed,
+                available,
+            } => write!(
+                f,
+                "unexpected end of input at byte {offset}: needed {needed} bytes, {available} available"
+            ),
+            FrameError::TrailingBytes { offset, remaining } => {
+                write!(f, "{remaining} trailing bytes at byte {offset}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+pub struct FrameReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> FrameReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        FrameReader { bytes, offset: 0 }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], FrameError> {
+        if len > self.remaining() {
+            return Err(FrameError::UnexpectedEnd {
+                offset: self.offset,
+                needed: len,
+                available: self.remaining(),
+            });
+        }
+
+        let bytes = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], FrameError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, FrameError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, FrameError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    //Reads a length-prefixed frame, and returns a reader over just its contents
+    pub fn read_frame(&mut self) -> Result<FrameReader<'a>, FrameError> {
+        let len = self.read_u32()? as usize;
+        Ok(FrameReader::new(self.read_bytes(len)?))
+    }
+
+    //Checks that all bytes have been read
+    pub fn finish(self) -> Result<(), FrameError> {
+        if self.remaining() > 0 {
+            return Err(FrameError::TrailingBytes {
+                offset: self.offset,
+                remaining: self.remaining(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameError, FrameReader};
+
+    #[test]
+    fn test_read_frames() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&[7, 8, 9]);
+        bytes.push(42);
+
+        let mut reader = FrameReader::new(&bytes);
+        let mut frame = reader.read_frame().unwrap();
+        assert_eq!(frame.read_bytes(3).unwrap(), &[7, 8, 9]);
+        frame.finish().unwrap();
+
+        assert_eq!(reader.read_u8().unwrap(), 42);
+        reader.finish().unwrap();
+    }
+
+    #[test]
+    fn test_bounds() {
+        //The frame claims 100 bytes, but only 2 follow
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&100u32.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2]);
+
+        let mut reader = FrameReader::new(&bytes);
+        assert_eq!(
+            reader.read_frame().err(),
+            Some(FrameError::UnexpectedEnd {
+                offset: 4,
+                needed: 100,
+                available: 2
+            })
+        );
+
+        let mut reader = FrameReader::new(&bytes[..2]);
+        assert!(reader.read_u32().is_err());
+
+        let mut reader = FrameReader::new(&bytes);
+        reader.read_u8().unwrap();
+        assert_eq!(
+            reader.finish(),
+            Err(FrameError::TrailingBytes {
+                offset: 1,
+                remaining: 5
+            })
+        );
+    }
+}
@@ -0,0 +1,127 @@
+/*
+Golden-file tests of the encoded formats.
+
+A fixed set of sequences is encoded with every codec, and the bytes are compared against the files
+in testdata/golden. Any change to the bytes an encoder writes fails these tests, even if the codec
+still round-trips.
+
+After an intended format change, run the tests with BLESS_GOLDEN=1 to rewrite the files, and check
+the diff in.
+*/
+
+use std::{env, fs, path::PathBuf};
+
+use crate::{
+    datagen,
+    simple16::Simple16Factory,
+    varint_gb::{DescriptorTable, VarintGBFactory},
+    varint_su::VarintSUFactory,
+};
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/golden")
+}
+
+fn check_golden(name: &str, bytes: &[u8]) {
+    let path = golden_dir().join(name);
+    if env::var_os("BLESS_GOLDEN").is_some() {
+        fs::create_dir_all(golden_dir()).unwrap();
+        fs::write(&path, bytes).unwrap();
+        return;
+    }
+
+    let expected = fs::read(&path).unwrap_or_else(|err| {
+        panic!(
+            "could not read {}: {err}, run with BLESS_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    if let Some(offset) = expected.iter().zip(bytes).position(|(a, b)| a != b) {
+        panic!("{name} differs from the golden file at byte {offset}");
+    }
+    assert_eq!(
+        bytes.len(),
+        expected.len(),
+        "{name} has a different length than the golden file"
+    );
+}
+
+//Small, fixed inputs that between them hit every value width of every codec
+fn corpus() -> Vec<(&'static str, Vec<u32>)> {
+    vec![
+        ("empty", vec![]),
+        ("single", vec![7]),
+        (
+            "mixed",
+            vec![1, 2, 3, 200, 70000, 70001, 20000000, 20000005, 20000006],
+        ),
+        ("alternating", datagen::gb_alternating_widths(101)),
+        ("max_width", datagen::gb_max_width(9)),
+        ("chain", datagen::su_continuation_chain(50, 3)),
+        ("runs", datagen::dense_runs(300, 40, 5000)),
+    ]
+}
+
+#[test]
+fn test_golden_varint_gb() {
+    for (name, values) in corpus() {
+        let mut fact = VarintGBFactory::new();
+        for val in values.iter() {
+            fact.push_int(*val);
+        }
+        let seq = fact.into_varint_gb();
+        check_golden(&format!("{name}.gb"), &seq.byte_stream);
+        check_golden(&format!("{name}.gb.serialized"), &seq.to_bytes());
+    }
+}
+
+//The corpus, and runs long enough to be escaped, which the corpus does not have
+#[test]
+fn test_golden_varint_gb_runs() {
+    let long_runs = ("long_runs", datagen::dense_runs(300, 100, 5000));
+    for (name, values) in corpus().into_iter().chain([long_runs]) {
+        let mut fact = VarintGBFactory::new_with_run_escapes();
+        for val in values.iter() {
+            fact.push_int(*val);
+        }
+        check_golden(
+            &format!("{name}.gb-runs.serialized"),
+            &fact.into_varint_gb().to_bytes(),
+        );
+    }
+}
+
+#[test]
+fn test_golden_varint_su() {
+    for (name, values) in corpus() {
+        let mut fact = VarintSUFactory::new();
+        for val in values.iter() {
+            fact.push_int(*val);
+        }
+        let seq = fact.into_varint_su();
+        check_golden(&format!("{name}.su"), &seq.bytes);
+        check_golden(&format!("{name}.su.serialized"), &seq.to_bytes());
+    }
+}
+
+#[test]
+fn test_golden_simple16() {
+    for (name, values) in corpus() {
+        let mut fact = Simple16Factory::new();
+        for val in values.iter() {
+            fact.push_int(*val);
+        }
+        let bytes: Vec<u8> = fact
+            .into_simple16()
+            .words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+        check_golden(&format!("{name}.s16"), &bytes);
+    }
+}
+
+#[test]
+fn test_golden_descriptor_table() {
+    check_golden("descriptor_table", &DescriptorTable::new().to_bytes());
+}
@@ -0,0 +1,307 @@
+/*
+Shuffle and length tables for group layouts other than the one of VarintGB, so experimental formats
+can reuse the table machinery of DescriptorTable.
+
+A layout is the number of values in a group, and the number of bytes each length code in the
+descriptor stands for. VarintGB groups hold 4 values with 2 bit codes for 1 to 4 bytes. A layout
+can instead map the codes to e.g. 0, 1, 2 and 4 bytes, so values of 0 take no bytes at all, or hold
+8 values with a 1 bit code each. The descriptor is always one byte, so groups of 4 values have 2 bit
+codes, and groups of 8 values 1 bit codes.
+
+Values are widened to u32. Groups of 8 values are shuffled 4 values at a time, so an entry holds a
+shuffle for each half of the group, and where the bytes of the half start in the group.
+*/
+
+use crate::simd::{shuffle_bytes, Backend, Shuffle};
+
+//The shuffle that moves up to four values, stored in the given numbers of bytes one after another,
+//into slots of width bytes. Bytes past the width are dropped, so only values that fit may be
+//shuffled into narrower slots.
+pub(crate) fn shuffle_for_lengths(lengths: &[u8], width: usize) -> Shuffle {
+    let mut shuffle = Shuffle([0xFF; 16]);
+    let mut input = 0;
+    for (i, len) in lengths.iter().enumerate() {
+        for n in 0..(*len as usize).min(width) {
+            shuffle.0[i * width + n] = (input + n) as u8;
+        }
+        input += *len as usize;
+    }
+    shuffle
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct LayoutEntry {
+    //One shuffle per four values, reading 16 bytes from its offset in the group
+    shuffles: [Shuffle; 2],
+    offsets: [u8; 2],
+    //Bytes of the group after the descriptor
+    length: u8,
+}
+
+pub struct DescriptorTableBuilder {
+    values_per_group: usize,
+    lengths: Vec<u8>,
+    backend: Backend,
+}
+
+impl DescriptorTableBuilder {
+    //The layout of VarintGB, decoded with the fastest backend the CPU supports
+    pub fn new() -> Self {
+        DescriptorTableBuilder {
+            values_per_group: 4,
+            lengths: vec![1, 2, 3, 4],
+            backend: Backend::detect(),
+        }
+    }
+
+    //4 values with 2 bit length codes, or 8 values with 1 bit codes
+    pub fn values_per_group(mut self, values: usize) -> Self {
+        self.values_per_group = values;
+        self
+    }
+
+    //The bytes each length code stands for, indexed by the code
+    pub fn lengths(mut self, lengths: &[u8]) -> Self {
+        self.lengths = lengths.to_vec();
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    //Panics if the group is not 4 or 8 values, there is not one length per code, a length is over
+    //4 bytes, or the CPU does not support the backend
+    pub fn build(self) -> LayoutTable {
+        assert!(
+            self.values_per_group == 4 || self.values_per_group == 8,
+            "groups hold 4 or 8 values"
+        );
+        let code_bits = 8 / self.values_per_group;
+        assert_eq!(
+            self.lengths.len(),
+            1 << code_bits,
+            "there must be one length per code"
+        );
+        assert!(
+            self.lengths.iter().all(|len| *len <= 4),
+            "values take at most 4 bytes"
+        );
+        assert!(
+            self.backend.is_supported(),
+            "{:?} is not supported",
+            self.backend
+        );
+
+        let entries = (0..=255)
+            .map(|descriptor: u8| {
+                let lengths: Vec<u8> = (0..self.values_per_group)
+                    .map(|i| {
+                        let code = descriptor >> (i * code_bits) & ((1 << code_bits) - 1);
+                        self.lengths[code as usize]
+                    })
+                    .collect();
+
+                let mut entry = LayoutEntry {
+                    shuffles: [Shuffle([0xFF; 16]); 2],
+                    offsets: [0; 2],
+                    length: 0,
+                };
+                for (half, half_lengths) in lengths.chunks(4).enumerate() {
+                    entry.shuffles[half] = shuffle_for_lengths(half_lengths, 4);
+                    entry.offsets[half] = entry.length;
+                    entry.length += half_lengths.iter().sum::<u8>();
+                }
+                entry
+            })
+            .collect();
+
+        LayoutTable {
+            entries,
+            values_per_group: self.values_per_group,
+            backend: self.backend,
+        }
+    }
+}
+
+pub struct LayoutTable {
+    entries: Vec<LayoutEntry>,
+    values_per_group: usize,
+    //Only ever set to a backend the CPU supports, which makes the shuffles safe to run
+    backend: Backend,
+}
+
+impl LayoutTable {
+    pub fn values_per_group(&self) -> usize {
+        self.values_per_group
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    //Bytes of the group after the descriptor
+    pub fn length(&self, descriptor: u8) -> usize {
+        self.entries[descriptor as usize].length as usize
+    }
+
+    //Decodes the group whose bytes, after the descriptor, start group. The first values_per_group
+    //values are returned, and the rest are 0. Halves that have 16 bytes left in group are loaded
+    //straight from it, and the others are copied out first.
+    pub fn decode_group(&self, descriptor: u8, group: &[u8]) -> [u32; 8] {
+        let entry = &self.entries[descriptor as usize];
+        let mut values = [0; 8];
+        for half in 0..self.values_per_group / 4 {
+            let offset = entry.offsets[half] as usize;
+            let mut padded = [0; 16];
+            let bytes = match group.get(offset..offset + 16) {
+                Some(bytes) => <&[u8; 16]>::try_from(bytes).unwrap(),
+                None => {
+                    let rest = &group[offset.min(group.len())..];
+                    padded[..rest.len()].copy_from_slice(rest);
+                    &padded
+                }
+            };
+            let shuffled = unsafe { shuffle_bytes(self.backend, bytes, &entry.shuffles[half]) };
+            let half_values = unsafe { std::mem::transmute::<[u8; 16], [u32; 4]>(shuffled) };
+            values[half * 4..half * 4 + 4].copy_from_slice(&half_values);
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use crate::{
+        simd::Backend,
+        varint_gb::{decode_chunk_safe_non_simd, DescriptorTable},
+    };
+
+    use super::{DescriptorTableBuilder, LayoutTable};
+
+    const BACKENDS: [Backend; 4] = [
+        Backend::Scalar,
+        Backend::Ssse3,
+        Backend::Avx2,
+        Backend::Neon,
+    ];
+
+    //Writes the values with the smallest code whose length holds them, and returns the descriptor
+    fn encode_group(lengths: &[u8], values: &[u32], output: &mut Vec<u8>) -> u8 {
+        let code_bits = 8 / values.len();
+        let mut descriptor = 0;
+        for (i, val) in values.iter().enumerate() {
+            let needed = 4 - val.leading_zeros() as u8 / 8;
+            let code = (0..lengths.len())
+                .filter(|code| lengths[*code] >= needed)
+                .min_by_key(|code| lengths[*code])
+                .unwrap();
+            descriptor |= (code as u8) << (i * code_bits);
+            output.extend_from_slice(&val.to_le_bytes()[..lengths[code] as usize]);
+        }
+        descriptor
+    }
+
+    fn check_layout(make_table: impl Fn(Backend) -> LayoutTable, lengths: &[u8], max: u32) {
+        let mut rng = rand::thread_rng();
+        for backend in BACKENDS
+            .into_iter()
+            .filter(|backend| backend.is_supported())
+        {
+            let table = make_table(backend);
+            let values_per_group = table.values_per_group();
+            for _ in 0..1000 {
+                let values: Vec<u32> = (0..values_per_group)
+                    .map(|_| rng.gen_range(0..=max) >> rng.gen_range(0..32))
+                    .collect();
+                let mut group = Vec::new();
+                let descriptor = encode_group(lengths, &values, &mut group);
+                assert_eq!(table.length(descriptor), group.len());
+
+                //At the end of a stream, and with bytes of the next group after it
+                let decoded = table.decode_group(descriptor, &group);
+                assert_eq!(decoded[..values_per_group], values);
+                assert!(decoded[values_per_group..].iter().all(|val| *val == 0));
+                group.extend_from_slice(&[0xAB; 32]);
+                assert_eq!(table.decode_group(descriptor, &group), decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_layout() {
+        //The default layout is the one of DescriptorTable
+        let table = DescriptorTableBuilder::new().build();
+        let descriptor_table = DescriptorTable::new();
+        let mut rng = rand::thread_rng();
+        for descriptor in 0..=255 {
+            assert_eq!(
+                table.length(descriptor),
+                descriptor_table.get_length_for_descriptor(descriptor) as usize
+            );
+            assert_eq!(
+                table.entries[descriptor as usize].shuffles[0],
+                descriptor_table.get_shuffle_for_descriptor(descriptor)
+            );
+            let group: [u8; 16] = rng.gen();
+            assert_eq!(
+                table.decode_group(descriptor, &group)[..4],
+                decode_chunk_safe_non_simd(descriptor, &group)
+            );
+        }
+
+        check_layout(
+            |backend| DescriptorTableBuilder::new().backend(backend).build(),
+            &[1, 2, 3, 4],
+            u32::MAX,
+        );
+    }
+
+    #[test]
+    fn test_other_layouts() {
+        //Values of 0 take no bytes
+        let lengths = [0, 1, 2, 4];
+        check_layout(
+            |backend| {
+                DescriptorTableBuilder::new()
+                    .lengths(&lengths)
+                    .backend(backend)
+                    .build()
+            },
+            &lengths,
+            u32::MAX,
+        );
+        let table = DescriptorTableBuilder::new().lengths(&lengths).build();
+        assert_eq!(table.length(0), 0);
+        assert_eq!(table.decode_group(0, &[]), [0; 8]);
+
+        //8 values with a 1 bit code each
+        for lengths in [[1, 4], [2, 3], [0, 1]] {
+            let max = if lengths[1] == 4 {
+                u32::MAX
+            } else {
+                (1 << (8 * lengths[1])) - 1
+            };
+            check_layout(
+                |backend| {
+                    DescriptorTableBuilder::new()
+                        .values_per_group(8)
+                        .lengths(&lengths)
+                        .backend(backend)
+                        .build()
+                },
+                &lengths,
+                max,
+            );
+        }
+        let table = DescriptorTableBuilder::new()
+            .values_per_group(8)
+            .lengths(&[1, 4])
+            .build();
+        assert_eq!(table.length(0), 8);
+        assert_eq!(table.length(0xFF), 32);
+    }
+}
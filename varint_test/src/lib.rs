@@ -0,0 +1,41 @@
+//The codecs, shared by the benchmark binary and the Criterion benches.
+//Every codec is behind its own feature (gb, su, s16, ef), and all-codecs, the default, enables them
+//all. The binary and the benches need all-codecs.
+//These lints only apply to exported items, and the codecs were written before they were exported.
+#![allow(
+    clippy::new_without_default,
+    clippy::len_without_is_empty,
+    clippy::missing_safety_doc
+)]
+
+#[cfg(not(any(feature = "gb", feature = "su", feature = "s16", feature = "ef")))]
+compile_error!("enable at least one codec feature: gb, su, s16 or ef");
+
+pub mod bench;
+#[cfg(feature = "gb")]
+pub mod bitmap;
+#[cfg(feature = "gb")]
+pub mod block_cursor;
+pub mod codec;
+pub mod datagen;
+#[cfg(feature = "ef")]
+pub mod elias_fano;
+pub mod error;
+pub mod frame;
+#[cfg(all(test, feature = "gb", feature = "su", feature = "s16"))]
+mod golden;
+#[cfg(feature = "gb")]
+pub mod layout;
+#[cfg(feature = "gb")]
+pub mod remap;
+pub mod set_ops;
+pub mod simd;
+#[cfg(feature = "s16")]
+pub mod simple16;
+pub mod transcode;
+#[cfg(feature = "gb")]
+pub mod varint_gb;
+#[cfg(feature = "gb")]
+pub mod varint_gb64;
+#[cfg(feature = "su")]
+pub mod varint_su;
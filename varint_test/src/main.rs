@@ -1,14 +1,85 @@
-use std::{hint::black_box, time::Instant};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    env,
+    error::Error,
+    fs,
+    hint::black_box,
+    io::{BufReader, BufWriter},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
 
 use itertools::Itertools;
 use rand::Rng;
-use varint_gb::{DescriptorTable, VarintGBFactory};
-use varint_su::VarintSUFactory;
+use varint_test::{
+    bench::{self, Codec},
+    bitmap::union_into_bitmap,
+    codec::{encode, IntSequenceCodec, IntSequenceFactory},
+    datagen,
+    elias_fano::EliasFanoFactory,
+    set_ops,
+    simd::Backend,
+    simple16::Simple16Factory,
+    transcode,
+    varint_gb::{self, auto_block_len, DecodeContext, DescriptorTable, VarintGBFactory},
+    varint_gb64::{PairTable, VarintGB64Factory},
+    varint_su::{self, VarintSUFactory},
+};
 
-mod varint_gb;
-mod varint_su;
+//Counts allocations, so the benchmark can show which decode paths allocate
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "bench") {
+        if let Err(err) = run_bench(&args[1..]) {
+            eprintln!("bench: {err}");
+            process::exit(1);
+        }
+        return;
+    }
+    if args.first().is_some_and(|arg| arg == "calibrate") {
+        if let Err(err) = run_calibrate(&args[1..]) {
+            eprintln!("calibrate: {err}");
+            process::exit(1);
+        }
+        return;
+    }
+    if args.first().is_some_and(|arg| arg == "transcode") {
+        match run_transcode(&args[1..]) {
+            Ok(true) => return,
+            Ok(false) => process::exit(2),
+            Err(err) => {
+                eprintln!("transcode: {err}");
+                process::exit(1);
+            }
+        }
+    }
+
+    time_descriptor_table();
+    compare_short_lists();
+    time_adversarial();
+    time_union();
+    time_intersect();
+    time_gb64();
+
     const SIZE: usize = 20000000;
     let repetitions = 100;
 
@@ -16,7 +87,7 @@ fn main() {
     let mut reference_vector = Vec::new();
     let mut seq_su_fact = VarintSUFactory::new();
     let mut seq_gb_fact = VarintGBFactory::new();
-    let shuffle_table = DescriptorTable::new();
+    let shuffle_table = DescriptorTable::shared();
 
     let mut rng = rand::thread_rng();
     for _ in 0..no_of_inserts {
@@ -24,7 +95,9 @@ fn main() {
     }
 
     reference_vector.sort();
-    reference_vector = reference_vector.iter().unique().map(|i| *i).collect();
+    reference_vector = reference_vector.iter().unique().copied().collect();
+
+    time_block_len_sweep(&reference_vector, &shuffle_table);
 
     //SU PUSH
     let su_start_push = Instant::now();
@@ -55,6 +128,19 @@ fn main() {
 
     let gb_decode_time = gb_start_decode.elapsed();
 
+    let gb_run_start_decode = Instant::now();
+    for _ in 0..repetitions {
+        black_box(seq_gb.get_values_run_aware(&shuffle_table));
+    }
+    let gb_run_decode_time = gb_run_start_decode.elapsed();
+
+    let scalar_table = DescriptorTable::with_backend(Backend::Scalar);
+    let gb_scalar_start_decode = Instant::now();
+    for _ in 0..repetitions {
+        black_box(seq_gb.get_values_run_aware(&scalar_table));
+    }
+    let gb_scalar_decode_time = gb_scalar_start_decode.elapsed();
+
     let ref_start_decode = Instant::now();
     for val in reference_vector.iter() {
         black_box(val);
@@ -75,19 +161,657 @@ fn main() {
         gb_decode_time.as_millis()
     );
 
+    println!(
+        "GB (run aware, into values, {:?}): ",
+        shuffle_table.backend()
+    );
+    println!("Decode-time: {}", gb_run_decode_time.as_millis());
+
+    println!("GB (run aware, into values, Scalar): ");
+    println!("Decode-time: {}", gb_scalar_decode_time.as_millis());
+
+    time_decode_context(&seq_gb, &shuffle_table, repetitions);
+    time_decode_into(&seq_gb, repetitions);
+
     println!("REF: ");
     println!("Decode-time: {}", ref_decode_time.as_millis());
 
     println!(" ");
 }
 
-fn time_varint_gb(seq_gb: &varint_gb::VarintGB, shuffle_table: &DescriptorTable) {
-    for chunk in seq_gb.iter_unsafe(&shuffle_table) {
+const BENCH_USAGE: &str = "usage: varint_test bench [--input FILE] [--format text|binary] \
+[--codec gb,gb-blocks,gb-runs,su,s16,ef] [--reps N] [--csv FILE]";
+
+//The bench subcommand: runs the chosen codecs on lists from a file, or on random lists without
+//--input. Files ending in .txt are read as text, others as binary, unless --format is given.
+fn run_bench(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut input = None;
+    let mut format = None;
+    let mut codecs = Codec::ALL.to_vec();
+    let mut reps = 10;
+    let mut csv = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or(format!("{arg} needs a value\n{BENCH_USAGE}"))
+        };
+        match arg.as_str() {
+            "--input" => input = Some(value()?),
+            "--format" => format = Some(value()?.as_str()),
+            "--codec" => {
+                codecs = value()?
+                    .split(',')
+                    .map(|name| Codec::from_name(name).ok_or(format!("unknown codec {name}")))
+                    .collect::<Result<_, _>>()?
+            }
+            "--reps" => reps = value()?.parse()?,
+            "--csv" => csv = Some(value()?),
+            _ => return Err(format!("unknown argument {arg}\n{BENCH_USAGE}").into()),
+        }
+    }
+
+    let lists = match input {
+        Some(path) => read_lists(path, format)?,
+        None => random_lists(1000, 1 << 24),
+    };
+
+    println!(
+        "BENCH ({} lists, {} integers, {reps} decodes): ",
+        lists.len(),
+        lists.iter().map(|list| list.len()).sum::<usize>()
+    );
+    let mut results = Vec::new();
+    for codec in codecs {
+        if !codec.supports(&lists) {
+            println!("{codec}: skipped, the lists have gaps it cannot store");
+            continue;
+        }
+
+        let result = bench::run(codec, &lists, reps);
+        println!(
+            "{codec}: bits/int: {:.3}, encode: {:.1} M ints/s, decode: {:.1} M ints/s",
+            result.bits_per_integer(),
+            result.encode_rate(),
+            result.decode_rate()
+        );
+        if codec == Codec::GbRuns {
+            let (runs, values) = bench::escaped_runs(&lists);
+            println!(
+                "{codec}: {runs} escaped runs, holding {:.1}% of the integers",
+                values as f64 * 100.0 / result.integers.max(1) as f64
+            );
+        }
+        results.push(result);
+    }
+
+    if let Some(path) = csv {
+        bench::write_csv(&results, &mut BufWriter::new(fs::File::create(path)?))?;
+    }
+    Ok(())
+}
+
+//Reads lists as text or binary. Files ending in .txt are read as text, others as binary, unless
+//format is given.
+fn read_lists(path: &str, format: Option<&str>) -> Result<Vec<Vec<u32>>, Box<dyn Error>> {
+    let text = match format {
+        Some("text") => true,
+        Some("binary") => false,
+        Some(format) => return Err(format!("unknown format {format}").into()),
+        None => path.ends_with(".txt"),
+    };
+    Ok(if text {
+        bench::read_text(BufReader::new(fs::File::open(path)?))?
+    } else {
+        bench::read_binary(&fs::read(path)?)?
+    })
+}
+
+const CALIBRATE_USAGE: &str =
+    "usage: varint_test calibrate --input FILE [--format text|binary] [--output FILE]";
+
+//The calibrate subcommand: fits a gap profile to the lists in a file, and prints it. With
+//--output, lists of the same lengths are drawn from the profile and written as binary, for the
+//bench subcommand. The profile of the drawn lists is printed too, to compare against.
+fn run_calibrate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut input = None;
+    let mut format = None;
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or(format!("{arg} needs a value\n{CALIBRATE_USAGE}"))
+        };
+        match arg.as_str() {
+            "--input" => input = Some(value()?),
+            "--format" => format = Some(value()?.as_str()),
+            "--output" => output = Some(value()?),
+            _ => return Err(format!("unknown argument {arg}\n{CALIBRATE_USAGE}").into()),
+        }
+    }
+    let path = input.ok_or(format!("--input is required\n{CALIBRATE_USAGE}"))?;
+    let lists = read_lists(path, format)?;
+
+    let profile = datagen::fit_gap_profile(&lists);
+    println!("CALIBRATE ({} lists): ", lists.len());
+    print_gap_profile("input", &profile);
+
+    if let Some(output) = output {
+        let mut rng = rand::thread_rng();
+        let synthetic: Vec<Vec<u32>> = lists
+            .iter()
+            .map(|list| datagen::calibrated_list(&profile, list.len(), &mut rng))
+            .collect();
+        bench::write_binary(&synthetic, &mut BufWriter::new(fs::File::create(output)?))?;
+        print_gap_profile("synthetic", &datagen::fit_gap_profile(&synthetic));
+    }
+    Ok(())
+}
+
+fn print_gap_profile(name: &str, profile: &datagen::GapProfile) {
+    println!(
+        "{name}: gaps: {}, power law exponent: {:.3}, max gap: {}, runs of 1: {}, \
+run start: {:.3}, mean run: {:.1}, longest run: {}",
+        profile.gaps,
+        profile.exponent,
+        profile.max_gap,
+        profile.runs,
+        profile.run_start,
+        profile.mean_run_len,
+        profile.longest_run
+    );
+}
+
+const TRANSCODE_USAGE: &str = "usage: varint_test transcode --input FILE \
+[--from text|binary|gb|gb-blocks|gb-runs|su] [--to gb,gb-blocks,gb-runs,su,s16,ef] [--output FILE]";
+
+//The transcode subcommand: reads lists in one format, encodes them with each target codec, and
+//reports the size and whether they decode to the input. With --output, the lists are written in
+//the single target codec, which must be serializable. Returns false if any list did not decode
+//to the input. Files ending in .txt are read as text, others as binary, unless --from is given.
+fn run_transcode(args: &[String]) -> Result<bool, Box<dyn Error>> {
+    let mut input = None;
+    let mut from = None;
+    let mut codecs = Codec::ALL.to_vec();
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or(format!("{arg} needs a value\n{TRANSCODE_USAGE}"))
+        };
+        match arg.as_str() {
+            "--input" => input = Some(value()?),
+            "--from" => from = Some(value()?.as_str()),
+            "--to" => {
+                codecs = value()?
+                    .split(',')
+                    .map(|name| Codec::from_name(name).ok_or(format!("unknown codec {name}")))
+                    .collect::<Result<_, _>>()?
+            }
+            "--output" => output = Some(value()?),
+            _ => return Err(format!("unknown argument {arg}\n{TRANSCODE_USAGE}").into()),
+        }
+    }
+    let path = input.ok_or(format!("--input is required\n{TRANSCODE_USAGE}"))?;
+    let output = match (output, &codecs[..]) {
+        (Some(output), [codec]) if transcode::is_serializable(*codec) => Some((output, *codec)),
+        (Some(_), [codec]) => return Err(format!("{codec} sequences cannot be serialized").into()),
+        (Some(_), _) => return Err("--output needs exactly one codec in --to".into()),
+        (None, _) => None,
+    };
+
+    let bytes = fs::read(path)?;
+    let from = from.unwrap_or(if path.ends_with(".txt") {
+        "text"
+    } else {
+        "binary"
+    });
+    let lists = match from {
+        "text" => bench::read_text(bytes.as_slice())?,
+        "binary" => bench::read_binary(&bytes)?,
+        name => {
+            let codec = Codec::from_name(name).ok_or(format!("unknown format {name}"))?;
+            transcode::read_serialized(codec, &bytes)?
+        }
+    };
+
+    println!(
+        "TRANSCODE ({} lists, {} integers, {} bytes as {from}): ",
+        lists.len(),
+        lists.iter().map(|list| list.len()).sum::<usize>(),
+        bytes.len()
+    );
+    let mut verified = true;
+    for codec in codecs.iter() {
+        if !codec.supports(&lists) {
+            println!("{codec}: skipped, the lists have gaps it cannot store");
+            continue;
+        }
+
+        let result = transcode::transcode(*codec, &lists);
+        let verify = if result.mismatched_lists == 0 {
+            "ok".to_string()
+        } else {
+            format!("{} lists differ", result.mismatched_lists)
+        };
+        println!(
+            "{codec}: bytes: {}, bits/int: {:.3}, verify: {verify}",
+            result.bytes,
+            result.bits_per_integer()
+        );
+        verified &= result.mismatched_lists == 0;
+    }
+
+    if let Some((path, codec)) = output {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        let written = transcode::write_serialized(codec, &lists, &mut writer)?;
+        println!("Wrote {written} bytes of {codec} sequences to {path}");
+    }
+    Ok(verified)
+}
+
+//Lists of 1 to 65536 values spread over the whole universe, so longer lists are denser, like in
+//a real index
+fn random_lists(lists: usize, universe: u32) -> Vec<Vec<u32>> {
+    let mut rng = rand::thread_rng();
+    (0..lists)
+        .map(|_| {
+            let len = 1 << rng.gen_range(0..17);
+            let max_gap = (universe / len).max(1);
+            let mut value = 0;
+            (0..len)
+                .map(|_| {
+                    value += rng.gen_range(1..=max_gap);
+                    value
+                })
+                .collect()
+        })
+        .collect()
+}
+
+//Average size in bytes of short lists with random gaps, as most lists in a real vocabulary are short
+fn compare_short_lists() {
+    let lists = 10000;
+    let mut rng = rand::thread_rng();
+
+    println!("SHORT LISTS (average bytes over {lists} lists): ");
+    for len in 1..=8 {
+        let reference_vectors: Vec<Vec<u32>> = (0..lists)
+            .map(|_| {
+                let mut value = 0;
+                (0..len)
+                    .map(|_| {
+                        value += rng.gen_range(1..100000);
+                        value
+                    })
+                    .collect()
+            })
+            .collect();
+
+        println!(
+            "Length: {}, GB: {:.2}, SU: {:.2}, S16: {:.2}, EF: {:.2}",
+            len,
+            average_size(VarintGBFactory::new, &reference_vectors),
+            average_size(VarintSUFactory::new, &reference_vectors),
+            average_size(Simple16Factory::new, &reference_vectors),
+            average_size(EliasFanoFactory::new, &reference_vectors)
+        );
+    }
+    println!(" ");
+}
+
+fn average_size<F: IntSequenceFactory>(
+    make_factory: impl Fn() -> F,
+    reference_vectors: &[Vec<u32>],
+) -> f64 {
+    let bytes: usize = reference_vectors
+        .iter()
+        .map(|values| encode(make_factory(), values).size_in_bytes())
+        .sum();
+    bytes as f64 / reference_vectors.len() as f64
+}
+
+//Size and decode time of every codec on the worst case inputs from datagen
+fn time_adversarial() {
+    let repetitions = 100;
+    let shuffle_table = DescriptorTable::shared();
+    let inputs = datagen::benchmark_inputs();
+
+    println!("ADVERSARIAL INPUTS ({repetitions} decodes): ");
+    for (name, reference_vector) in inputs.iter() {
+        let mut seq_gb_fact = VarintGBFactory::new();
+        let mut seq_su_fact = VarintSUFactory::new();
+        let mut seq_s16_fact = Simple16Factory::new();
+        for val in reference_vector.iter() {
+            seq_gb_fact.push_int(*val);
+            seq_su_fact.push_int(*val);
+            seq_s16_fact.push_int(*val);
+        }
+        let seq_gb = seq_gb_fact.into_varint_gb();
+        let seq_su = seq_su_fact.into_varint_su();
+        let seq_s16 = seq_s16_fact.into_simple16();
+
+        let gb_start = Instant::now();
+        for _ in 0..repetitions {
+            black_box(seq_gb.get_values_run_aware(&shuffle_table));
+        }
+        let gb_time = gb_start.elapsed();
+
+        let su_start = Instant::now();
+        for _ in 0..repetitions {
+            time_varint_su(&seq_su);
+        }
+        let su_time = su_start.elapsed();
+
+        let s16_time = time_values(&seq_s16, repetitions);
+
+        let seq_ef = encode(EliasFanoFactory::new(), reference_vector);
+        let ef_time = time_values(&seq_ef, repetitions);
+
+        println!("{name} ({} values): ", reference_vector.len());
+        println!(
+            "GB: {} bytes, {} ms, SU: {} bytes, {} ms, S16: {} bytes, {} ms, EF: {} bytes, {} ms",
+            seq_gb.byte_stream.len(),
+            gb_time.as_millis(),
+            seq_su.bytes.len(),
+            su_time.as_millis(),
+            seq_s16.size_in_bytes(),
+            s16_time.as_millis(),
+            seq_ef.size_in_bytes(),
+            ef_time.as_millis()
+        );
+    }
+    println!(" ");
+}
+
+//Unions many lists of mixed density, into a bitmap and by merging them into a sorted Vec
+fn time_union() {
+    let repetitions = 10;
+    let universe = 1 << 22;
+    let shuffle_table = DescriptorTable::shared();
+    let mut rng = rand::thread_rng();
+
+    let lists: Vec<varint_gb::VarintGB<'static>> = (0..64)
+        .map(|list| {
+            let mut seq_gb_fact = VarintGBFactory::new();
+            let mut value = 0;
+            loop {
+                value += rng.gen_range(1..=2 << (list % 8));
+                if value >= universe {
+                    break;
+                }
+                seq_gb_fact.push_int(value);
+            }
+            seq_gb_fact.into_varint_gb()
+        })
+        .collect();
+    let values: usize = lists.iter().map(|seq| seq.len()).sum();
+
+    let bitmap_start = Instant::now();
+    let mut count = 0;
+    for _ in 0..repetitions {
+        count = black_box(union_into_bitmap(&lists, &shuffle_table)).count();
+    }
+    let bitmap_time = bitmap_start.elapsed();
+
+    let merge_start = Instant::now();
+    for _ in 0..repetitions {
+        let decoded: Vec<Vec<u32>> = lists
+            .iter()
+            .map(|seq| seq.get_values_run_aware(&shuffle_table))
+            .collect();
+        let merged: Vec<u32> = decoded.into_iter().kmerge().dedup().collect();
+        black_box(merged);
+    }
+    let merge_time = merge_start.elapsed();
+
+    println!(
+        "UNION ({} lists, {values} values, {count} distinct, {repetitions} times): ",
+        lists.len()
+    );
+    println!(
+        "Bitmap-time: {}, merge-time: {}",
+        bitmap_time.as_millis(),
+        merge_time.as_millis()
+    );
+    println!(" ");
+}
+
+//Intersects a short list with long lists, on the compressed lists and by decoding both
+fn time_intersect() {
+    let repetitions = 100;
+    let shuffle_table = DescriptorTable::shared();
+    let mut rng = rand::thread_rng();
+
+    let mut short_fact = VarintGBFactory::new_block_aligned();
+    let mut value = 0;
+    for _ in 0..1000 {
+        value += rng.gen_range(1..4000);
+        short_fact.push_int(value);
+    }
+    let short = short_fact.into_varint_gb();
+
+    println!("INTERSECT (1000 values against a long list, {repetitions} times): ");
+    for max_gap in [2, 8, 64] {
+        let mut long_fact = VarintGBFactory::new_block_aligned();
+        let mut value = 0;
+        while value < short.top() {
+            value += rng.gen_range(1..=max_gap);
+            long_fact.push_int(value);
+        }
+        let long = long_fact.into_varint_gb();
+
+        let cursor_start = Instant::now();
+        for _ in 0..repetitions {
+            black_box(set_ops::intersect(&short, &long, &shuffle_table));
+        }
+        let cursor_time = cursor_start.elapsed();
+
+        let decode_start = Instant::now();
+        for _ in 0..repetitions {
+            let long_values = long.get_values_run_aware(&shuffle_table);
+            let short_values = short.get_values_run_aware(&shuffle_table);
+            let common: Vec<u32> = short_values
+                .iter()
+                .filter(|val| long_values.binary_search(val).is_ok())
+                .copied()
+                .collect();
+            black_box(common);
+        }
+        let decode_time = decode_start.elapsed();
+
+        println!(
+            "Long values: {}, cursor-time: {}, decode-time: {}",
+            long.len(),
+            cursor_time.as_millis(),
+            decode_time.as_millis()
+        );
+    }
+    println!(" ");
+}
+
+//Size and decode time of 64 bit values, whose gaps take 1 to 6 bytes
+fn time_gb64() {
+    let repetitions = 100;
+    let mut rng = rand::thread_rng();
+    let mut reference_vector = Vec::new();
+    let mut value: u64 = 0;
+    for _ in 0..1000000 {
+        let bits = rng.gen_range(0..48);
+        value += rng.gen_range(1..=1 << bits);
+        reference_vector.push(value);
+    }
+
+    let mut seq_gb64_fact = VarintGB64Factory::new();
+    for val in reference_vector.iter() {
+        seq_gb64_fact.push_int(*val);
+    }
+    let seq_gb64 = seq_gb64_fact.into_varint_gb64();
+
+    println!(
+        "GB64 ({} values, top {}, {} bytes, {repetitions} decodes): ",
+        seq_gb64.len(),
+        value,
+        seq_gb64.byte_stream.len()
+    );
+    for pair_table in [PairTable::new(), PairTable::with_backend(Backend::Scalar)] {
+        let start = Instant::now();
+        for _ in 0..repetitions {
+            black_box(seq_gb64.get_values(&pair_table));
+        }
+        println!(
+            "{:?} decode-time: {}",
+            pair_table.backend(),
+            start.elapsed().as_millis()
+        );
+    }
+    println!(" ");
+}
+
+//Time to iterate every value of the sequence, repetitions times
+fn time_values<C: IntSequenceCodec>(seq: &C, repetitions: usize) -> Duration {
+    let start = Instant::now();
+    for _ in 0..repetitions {
+        for val in seq.values() {
+            black_box(val);
+        }
+    }
+    start.elapsed()
+}
+
+fn time_descriptor_table() {
+    let repetitions = 1000;
+
+    let new_start = Instant::now();
+    for _ in 0..repetitions {
+        black_box(DescriptorTable::new());
+    }
+    let new_time = new_start.elapsed();
+
+    let bytes = DescriptorTable::new().to_bytes();
+    let load_start = Instant::now();
+    for _ in 0..repetitions {
+        black_box(DescriptorTable::from_bytes(&bytes).unwrap());
+    }
+    let load_time = load_start.elapsed();
+
+    println!("DESCRIPTOR TABLE ({repetitions} times): ");
+    println!(
+        "New-time: {}, load-time: {}",
+        new_time.as_micros(),
+        load_time.as_micros()
+    );
+    println!(" ");
+}
+
+//Times lookups of the first value >= a random target, which find the block from the headers and
+//decode only that block, for a range of block lengths
+fn time_block_len_sweep(reference_vector: &[u32], shuffle_table: &DescriptorTable) {
+    let lookups = 100000;
+    let mut rng = rand::thread_rng();
+    let top = *reference_vector.last().unwrap();
+    let targets: Vec<u32> = (0..lookups).map(|_| rng.gen_range(1..=top)).collect();
+
+    let auto = auto_block_len(reference_vector.len());
+    println!("BLOCK LENGTH SWEEP ({lookups} lookups, auto = {auto}): ");
+    for block_len in [16, 32, 64, 128, 256, 512, 1024, auto] {
+        let mut seq_gb_fact = VarintGBFactory::new_with_block_len(block_len);
+        for val in reference_vector.iter() {
+            seq_gb_fact.push_int(*val);
+        }
+        let seq_gb = seq_gb_fact.into_varint_gb();
+
+        let lookup_start = Instant::now();
+        for target in targets.iter() {
+            let block = seq_gb.block_for_value(*target).unwrap();
+            let values = seq_gb.decode_block(block, shuffle_table);
+            black_box(values.iter().find(|val| **val >= *target));
+        }
+        let lookup_time = lookup_start.elapsed();
+
+        println!(
+            "Block length: {}, bytes: {}, headers: {}, lookup-time: {}",
+            block_len,
+            seq_gb.byte_stream.len(),
+            seq_gb.blocks().len(),
+            lookup_time.as_millis()
+        );
+    }
+    println!(" ");
+}
+
+//Decodes the sequence repeatedly, allocating a new Vec each time, and through one DecodeContext
+fn time_decode_context(
+    seq_gb: &varint_gb::VarintGB<'_>,
+    shuffle_table: &DescriptorTable,
+    repetitions: usize,
+) {
+    let allocations_start = ALLOCATIONS.load(Ordering::Relaxed);
+    let vec_start = Instant::now();
+    for _ in 0..repetitions {
+        black_box(seq_gb.get_values_run_aware(shuffle_table));
+    }
+    let vec_time = vec_start.elapsed();
+    let vec_allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_start;
+
+    let mut context = DecodeContext::new(shuffle_table);
+    context.decode(seq_gb);
+    let allocations_start = ALLOCATIONS.load(Ordering::Relaxed);
+    let context_start = Instant::now();
+    for _ in 0..repetitions {
+        black_box(context.decode(seq_gb));
+    }
+    let context_time = context_start.elapsed();
+    let context_allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_start;
+
+    println!("GB (new Vec per decode): ");
+    println!(
+        "Decode-time: {}, allocations: {}",
+        vec_time.as_millis(),
+        vec_allocations
+    );
+    println!("GB (DecodeContext, after warmup): ");
+    println!(
+        "Decode-time: {}, allocations: {}",
+        context_time.as_millis(),
+        context_allocations
+    );
+}
+
+//decode_into with each backend that shuffles in SIMD, reusing one output buffer
+fn time_decode_into(seq_gb: &varint_gb::VarintGB<'_>, repetitions: usize) {
+    let mut output = Vec::with_capacity(seq_gb.len());
+    for backend in [Backend::Avx2, Backend::Ssse3, Backend::Neon] {
+        if !backend.is_supported() {
+            continue;
+        }
+        let shuffle_table = DescriptorTable::with_backend(backend);
+
+        let start = Instant::now();
+        for _ in 0..repetitions {
+            output.clear();
+            seq_gb.decode_into(&mut output, &shuffle_table);
+            black_box(&output);
+        }
+        let decode_time = start.elapsed();
+
+        println!("GB (decode_into, {backend:?}): ");
+        println!("Decode-time: {}", decode_time.as_millis());
+    }
+}
+
+fn time_varint_gb(seq_gb: &varint_gb::VarintGB<'_>, shuffle_table: &DescriptorTable) {
+    for chunk in seq_gb.iter_unsafe(shuffle_table) {
         black_box(chunk);
     }
 }
 
-fn time_varint_su(seq_su: &varint_su::VarintSU) {
+fn time_varint_su(seq_su: &varint_su::VarintSU<'_>) {
     for val in seq_su.iter() {
         black_box(val);
     }
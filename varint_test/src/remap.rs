@@ -0,0 +1,129 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::varint_gb::{DescriptorTable, VarintGB, VarintGBFactory};
+
+/*
+Applies a permutation of the value universe (e.g. a docID reordering) to a compressed sequence.
+
+The sequence is decoded and remapped in runs of at most run_len values. Each run is sorted and
+compressed again on its own, so only one run is held uncompressed at a time. The sorted runs are
+then merged into the output with a k-way merge over their iterators.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemapProgress {
+    //Values remapped and written to sorted runs so far
+    Remapped(usize),
+    //Values merged into the output so far
+    Merged(usize),
+}
+
+pub fn remap_varint_gb<F: FnMut(RemapProgress)>(
+    seq: &VarintGB<'_>,
+    permutation: &[u32],
+    descriptor_table: &DescriptorTable,
+    run_len: usize,
+    mut progress: F,
+) -> VarintGB<'static> {
+    assert!(run_len > 0);
+
+    let mut runs = Vec::new();
+    let mut run = Vec::with_capacity(run_len);
+    let mut remapped = 0;
+    for val in seq.iter_values(descriptor_table) {
+        run.push(permutation[val as usize]);
+        if run.len() == run_len {
+            remapped += run.len();
+            runs.push(sorted_run(&mut run));
+            progress(RemapProgress::Remapped(remapped));
+        }
+    }
+    if !run.is_empty() {
+        remapped += run.len();
+        runs.push(sorted_run(&mut run));
+        progress(RemapProgress::Remapped(remapped));
+    }
+
+    let mut run_iters: Vec<_> = runs
+        .iter()
+        .map(|run| run.iter_values(descriptor_table))
+        .collect();
+    let mut heap = BinaryHeap::with_capacity(run_iters.len());
+    for (run_index, run_iter) in run_iters.iter_mut().enumerate() {
+        if let Some(val) = run_iter.next() {
+            heap.push(Reverse((val, run_index)));
+        }
+    }
+
+    let mut factory = VarintGBFactory::new();
+    let mut merged = 0;
+    while let Some(Reverse((val, run_index))) = heap.pop() {
+        factory.push_int(val);
+        merged += 1;
+        if merged % run_len == 0 {
+            progress(RemapProgress::Merged(merged));
+        }
+        if let Some(next) = run_iters[run_index].next() {
+            heap.push(Reverse((next, run_index)));
+        }
+    }
+    if merged % run_len != 0 {
+        progress(RemapProgress::Merged(merged));
+    }
+
+    factory.into_varint_gb()
+}
+
+fn sorted_run(run: &mut Vec<u32>) -> VarintGB<'static> {
+    run.sort_unstable();
+    let mut factory = VarintGBFactory::new();
+    for val in run.drain(..) {
+        factory.push_int(val);
+    }
+    factory.into_varint_gb()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::seq::SliceRandom;
+
+    use crate::varint_gb::{DescriptorTable, VarintGBFactory};
+
+    use super::{remap_varint_gb, RemapProgress};
+
+    #[test]
+    fn test_remap() {
+        let shuffle_table = DescriptorTable::new();
+        let mut rng = rand::thread_rng();
+
+        let universe = 5000;
+        let mut permutation: Vec<u32> = (0..universe).collect();
+        permutation.shuffle(&mut rng);
+
+        let values: Vec<u32> = (1..universe).step_by(3).collect();
+        let mut seq_factory = VarintGBFactory::new();
+        for val in values.iter() {
+            seq_factory.push_int(*val);
+        }
+        let seq = seq_factory.into_varint_gb();
+
+        let mut progress = Vec::new();
+        let remapped = remap_varint_gb(&seq, &permutation, &shuffle_table, 100, |p| {
+            progress.push(p)
+        });
+
+        let mut reference_vector: Vec<u32> = values
+            .iter()
+            .map(|val| permutation[*val as usize])
+            .collect();
+        reference_vector.sort();
+        assert_eq!(
+            remapped.get_values_run_aware(&shuffle_table),
+            reference_vector
+        );
+
+        assert_eq!(progress.len(), 2 * values.len().div_ceil(100));
+        assert_eq!(progress[0], RemapProgress::Remapped(100));
+        assert_eq!(progress.last(), Some(&RemapProgress::Merged(values.len())));
+    }
+}
@@ -0,0 +1,651 @@
+/*
+Intersection, union and difference of sorted lists, computed on their cursors without decoding
+either list into a Vec first.
+
+Differences are driven by one list, and skip through the other with next_geq. Unions have to visit
+every value of both lists, so they merge the two cursors.
+
+Intersections switch between two modes as they go, since the density of two lists often varies
+along them. The zipper steps whichever cursor is behind to its next value, which is fastest where
+the lists are about as dense. Galloping steps through the sparser list, and seeks in the denser
+one with next_geq, so the denser one is only decoded around the values of the sparser one: GB
+skips whole blocks with its block headers, and SU jumps to restart points. The zipper gallops once
+one cursor falls behind many times in a row, and galloping goes back to the zipper once its seeks
+keep landing close by. A list much longer than the other starts out galloping. IntersectStats
+counts how often each mode ran.
+
+The results are written to any factory, so the lists and the result can be of different codecs.
+
+Before intersecting two GB lists, intersection_upper_bound bounds the size of the result from the
+block headers alone: a common value must lie in a block of each list, and the value ranges of the
+two blocks must overlap. The bound sizes the output, and an intersection with a bound of 0 is
+skipped.
+*/
+
+use crate::codec::IntSequenceFactory;
+#[cfg(feature = "su")]
+use crate::varint_su;
+#[cfg(feature = "gb")]
+use crate::{
+    block_cursor::BlockCursor,
+    codec::GapMode,
+    varint_gb::{DescriptorTable, ValueIter, VarintGB, VarintGBFactory},
+};
+
+//A cursor over a sorted list, that can skip ahead
+pub trait SortedCursor {
+    fn next_value(&mut self) -> Option<u32>;
+
+    //Returns the first value >= target at or after the current position
+    fn next_geq(&mut self, target: u32) -> Option<u32>;
+
+    //The index of the next value
+    fn position(&self) -> usize;
+}
+
+#[cfg(feature = "gb")]
+impl SortedCursor for ValueIter<'_, '_> {
+    fn next_value(&mut self) -> Option<u32> {
+        self.next()
+    }
+
+    fn next_geq(&mut self, target: u32) -> Option<u32> {
+        self.next_geq(target)
+    }
+
+    fn position(&self) -> usize {
+        self.mark().ordinal as usize
+    }
+}
+
+#[cfg(feature = "gb")]
+impl SortedCursor for BlockCursor<'_, '_> {
+    fn next_value(&mut self) -> Option<u32> {
+        self.next()
+    }
+
+    fn next_geq(&mut self, target: u32) -> Option<u32> {
+        self.next_geq(target)
+    }
+
+    fn position(&self) -> usize {
+        self.mark().ordinal as usize
+    }
+}
+
+#[cfg(feature = "su")]
+impl SortedCursor for varint_su::Iter<'_> {
+    fn next_value(&mut self) -> Option<u32> {
+        self.next().map(|val| val as u32)
+    }
+
+    fn next_geq(&mut self, target: u32) -> Option<u32> {
+        self.next_geq(target).map(|val| val as u32)
+    }
+
+    fn position(&self) -> usize {
+        self.mark().ordinal as usize
+    }
+}
+
+//A list at least this many times longer than the other starts out galloping
+const GALLOP_LEN_RATIO: usize = 8;
+//Times in a row the zipper steps the same cursor before the other one drives a gallop
+const GALLOP_AFTER_RUN: usize = 8;
+//A seek that moves past at most this many values is short
+const SHORT_SEEK: usize = 2;
+//Short seeks, or steps of the driving cursor, in a row before going back to the zipper
+const ZIPPER_AFTER_SHORT_SEEKS: usize = 4;
+
+//How often each mode of an intersection ran
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntersectStats {
+    //Steps of the zipper, to the next value of either cursor
+    pub zipper_steps: usize,
+    //Seeks with next_geq while galloping
+    pub gallop_seeks: usize,
+    //Changes between the two modes
+    pub switches: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Side {
+    A,
+    B,
+}
+
+impl Side {
+    fn other(self) -> Self {
+        match self {
+            Side::A => Side::B,
+            Side::B => Side::A,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Mode {
+    Zipper,
+    //The side that steps through its values, while the other one seeks
+    Gallop(Side),
+}
+
+//The values in both GB lists. The result is block aligned like a.
+#[cfg(feature = "gb")]
+pub fn intersect(a: &VarintGB<'_>, b: &VarintGB<'_>, table: &DescriptorTable) -> VarintGB<'static> {
+    assert!(
+        a.gap_mode() != GapMode::Absolute && b.gap_mode() != GapMode::Absolute,
+        "absolute values are not sorted"
+    );
+    let mut factory = factory_like(a);
+    let bound = intersection_upper_bound(a, b);
+    if bound == 0 {
+        return factory.into_varint_gb();
+    }
+    factory.reserve(bound);
+
+    let a_iter = a.iter_values(table);
+    let b_iter = b.iter_values(table);
+    intersect_cursors((a_iter, a.len()), (b_iter, b.len()), factory)
+}
+
+//An upper bound on the number of values in both GB lists, from their block headers. Lists
+//without blocks count as one block, from their first to their top value.
+#[cfg(feature = "gb")]
+pub fn intersection_upper_bound(a: &VarintGB<'_>, b: &VarintGB<'_>) -> usize {
+    //Absolute values are not sorted, so their blocks say nothing about them
+    if a.gap_mode() == GapMode::Absolute || b.gap_mode() == GapMode::Absolute {
+        return a.len().min(b.len());
+    }
+
+    let a_ranges = block_ranges(a);
+    let b_ranges = block_ranges(b);
+    overlapping_values(&a_ranges, &b_ranges).min(overlapping_values(&b_ranges, &a_ranges))
+}
+
+//The lowest and highest value each block may hold, and its number of values
+#[cfg(feature = "gb")]
+fn block_ranges(seq: &VarintGB<'_>) -> Vec<(u32, u32, usize)> {
+    let Some(first) = seq.first() else {
+        return Vec::new();
+    };
+    if seq.blocks().is_empty() {
+        return vec![(first, seq.top(), seq.len())];
+    }
+
+    let mut low = first;
+    let mut remaining = seq.len();
+    seq.blocks()
+        .iter()
+        .map(|header| {
+            let values = remaining.min(seq.block_len());
+            remaining -= values;
+            let range = (low, header.last_value, values);
+            //Values can repeat, so the next block may start with the last value of this one
+            low = header.last_value;
+            range
+        })
+        .collect()
+}
+
+//The number of values in the ranges of x that overlap any range of y. Both are sorted.
+#[cfg(feature = "gb")]
+fn overlapping_values(x: &[(u32, u32, usize)], y: &[(u32, u32, usize)]) -> usize {
+    let mut count = 0;
+    let mut y_index = 0;
+    for &(low, high, values) in x.iter() {
+        //Ranges of y that end before this one cannot overlap any later range of x either
+        while y_index < y.len() && y[y_index].1 < low {
+            y_index += 1;
+        }
+        if y_index == y.len() {
+            break;
+        }
+        if y[y_index].0 <= high {
+            count += values;
+        }
+    }
+    count
+}
+
+//The values in either GB list. The result is block aligned like a.
+#[cfg(feature = "gb")]
+pub fn union(a: &VarintGB<'_>, b: &VarintGB<'_>, table: &DescriptorTable) -> VarintGB<'static> {
+    assert!(
+        a.gap_mode() != GapMode::Absolute && b.gap_mode() != GapMode::Absolute,
+        "absolute values are not sorted"
+    );
+    union_cursors(a.iter_values(table), b.iter_values(table), factory_like(a))
+}
+
+//The values of a that are not in b. The result is block aligned like a.
+#[cfg(feature = "gb")]
+pub fn difference(
+    a: &VarintGB<'_>,
+    b: &VarintGB<'_>,
+    table: &DescriptorTable,
+) -> VarintGB<'static> {
+    assert!(
+        a.gap_mode() != GapMode::Absolute && b.gap_mode() != GapMode::Absolute,
+        "absolute values are not sorted"
+    );
+    difference_cursors(a.iter_values(table), b.iter_values(table), factory_like(a))
+}
+
+#[cfg(feature = "gb")]
+fn factory_like(seq: &VarintGB<'_>) -> VarintGBFactory {
+    if seq.block_len() > 0 {
+        VarintGBFactory::new_with_block_len(seq.block_len())
+    } else {
+        VarintGBFactory::new()
+    }
+}
+
+//Intersects two cursors, each given with the length of its list, which picks the starting mode
+pub fn intersect_cursors<A: SortedCursor, B: SortedCursor, F: IntSequenceFactory>(
+    a: (A, usize),
+    b: (B, usize),
+    factory: F,
+) -> F::Output {
+    intersect_cursors_with_stats(a, b, factory).0
+}
+
+pub fn intersect_cursors_with_stats<A: SortedCursor, B: SortedCursor, F: IntSequenceFactory>(
+    (mut a, a_len): (A, usize),
+    (mut b, b_len): (B, usize),
+    mut factory: F,
+) -> (F::Output, IntersectStats) {
+    let mut stats = IntersectStats::default();
+    let mut mode = if a_len.saturating_mul(GALLOP_LEN_RATIO) <= b_len {
+        Mode::Gallop(Side::A)
+    } else if b_len.saturating_mul(GALLOP_LEN_RATIO) <= a_len {
+        Mode::Gallop(Side::B)
+    } else {
+        Mode::Zipper
+    };
+    //The zipper counts the steps of run_side in a row, and galloping counts short seeks in a row
+    let mut run_side = Side::A;
+    let mut run = 0;
+
+    let (Some(mut a_head), Some(mut b_head)) = (a.next_value(), b.next_value()) else {
+        return (factory.finish(), stats);
+    };
+    loop {
+        if a_head == b_head {
+            factory.push(a_head);
+            match (a.next_value(), b.next_value()) {
+                (Some(a_next), Some(b_next)) => (a_head, b_head) = (a_next, b_next),
+                _ => break,
+            }
+            continue;
+        }
+
+        let behind = if a_head < b_head { Side::A } else { Side::B };
+        let seek = mode == Mode::Gallop(behind.other());
+        let (next, moved) = match behind {
+            Side::A => advance(&mut a, b_head, seek),
+            Side::B => advance(&mut b, a_head, seek),
+        };
+        let Some(next) = next else {
+            break;
+        };
+        match behind {
+            Side::A => a_head = next,
+            Side::B => b_head = next,
+        }
+
+        match mode {
+            Mode::Zipper => {
+                stats.zipper_steps += 1;
+                if behind == run_side {
+                    run += 1;
+                } else {
+                    run_side = behind;
+                    run = 1;
+                }
+                //The cursor that keeps falling behind is the denser one here, so it seeks
+                if run >= GALLOP_AFTER_RUN {
+                    mode = Mode::Gallop(behind.other());
+                    stats.switches += 1;
+                    run = 0;
+                }
+            }
+            Mode::Gallop(_) => {
+                if seek {
+                    stats.gallop_seeks += 1;
+                }
+                if !seek || moved <= SHORT_SEEK {
+                    run += 1;
+                } else {
+                    run = 0;
+                }
+                if run >= ZIPPER_AFTER_SHORT_SEEKS {
+                    mode = Mode::Zipper;
+                    stats.switches += 1;
+                    run = 0;
+                }
+            }
+        }
+    }
+    (factory.finish(), stats)
+}
+
+//Moves a cursor that is behind to its next value, or seeks it to the target. Returns the value,
+//and how many values the cursor moved past.
+fn advance<C: SortedCursor>(cursor: &mut C, target: u32, seek: bool) -> (Option<u32>, usize) {
+    if !seek {
+        return (cursor.next_value(), 1);
+    }
+    let before = cursor.position();
+    let next = cursor.next_geq(target);
+    (next, cursor.position() - before)
+}
+
+pub fn union_cursors<A: SortedCursor, B: SortedCursor, F: IntSequenceFactory>(
+    mut a: A,
+    mut b: B,
+    mut factory: F,
+) -> F::Output {
+    let mut a_head = a.next_value();
+    let mut b_head = b.next_value();
+    loop {
+        match (a_head, b_head) {
+            (Some(a_val), Some(b_val)) => {
+                factory.push(a_val.min(b_val));
+                if a_val <= b_val {
+                    a_head = a.next_value();
+                }
+                if b_val <= a_val {
+                    b_head = b.next_value();
+                }
+            }
+            (Some(a_val), None) => {
+                factory.push(a_val);
+                a_head = a.next_value();
+            }
+            (None, Some(b_val)) => {
+                factory.push(b_val);
+                b_head = b.next_value();
+            }
+            (None, None) => return factory.finish(),
+        }
+    }
+}
+
+//The values of a that are not in b, driven by a
+pub fn difference_cursors<A: SortedCursor, B: SortedCursor, F: IntSequenceFactory>(
+    mut a: A,
+    mut b: B,
+    mut factory: F,
+) -> F::Output {
+    let mut b_head = b.next_value();
+    while let Some(val) = a.next_value() {
+        if b_head.is_some_and(|head| head < val) {
+            b_head = b.next_geq(val);
+        }
+        if b_head != Some(val) {
+            factory.push(val);
+        }
+    }
+    factory.finish()
+}
+
+#[cfg(all(test, feature = "gb", feature = "su"))]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use rand::Rng;
+
+    use crate::{
+        codec::IntSequenceCodec,
+        varint_gb::{DescriptorTable, VarintGB, VarintGBFactory},
+        varint_su::VarintSUFactory,
+    };
+
+    use super::{
+        difference, difference_cursors, intersect, intersect_cursors, intersect_cursors_with_stats,
+        intersection_upper_bound, union, union_cursors,
+    };
+
+    fn absolute_list() -> VarintGB<'static> {
+        let mut fact = VarintGBFactory::new_absolute();
+        for val in [100, 5, 3] {
+            fact.push_int(val);
+        }
+        fact.into_varint_gb()
+    }
+
+    fn sorted_list() -> VarintGB<'static> {
+        let mut fact = VarintGBFactory::new();
+        for val in [3, 5, 100] {
+            fact.push_int(val);
+        }
+        fact.into_varint_gb()
+    }
+
+    //Set operations merge by value, so absolute lists are rejected
+    #[test]
+    #[should_panic(expected = "absolute values are not sorted")]
+    fn test_absolute_intersect() {
+        intersect(&sorted_list(), &absolute_list(), &DescriptorTable::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "absolute values are not sorted")]
+    fn test_absolute_union() {
+        union(&absolute_list(), &sorted_list(), &DescriptorTable::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "absolute values are not sorted")]
+    fn test_absolute_difference() {
+        difference(&absolute_list(), &sorted_list(), &DescriptorTable::new());
+    }
+
+    fn random_set(len: usize, max_gap: u32) -> BTreeSet<u32> {
+        let mut rng = rand::thread_rng();
+        let mut value = 0;
+        (0..len)
+            .map(|_| {
+                value += rng.gen_range(1..=max_gap);
+                value
+            })
+            .collect()
+    }
+
+    fn encode_gb(set: &BTreeSet<u32>, block_aligned: bool) -> VarintGB<'static> {
+        let mut fact = if block_aligned {
+            VarintGBFactory::new_block_aligned()
+        } else {
+            VarintGBFactory::new()
+        };
+        for val in set.iter() {
+            fact.push_int(*val);
+        }
+        fact.into_varint_gb()
+    }
+
+    #[test]
+    fn test_gb_set_ops() {
+        let shuffle_table = DescriptorTable::new();
+
+        //A short list against a long one, lists of similar length, and empty lists
+        for (a_len, a_gap, b_len, b_gap) in [
+            (20, 5000, 10000, 10),
+            (10000, 10, 20, 5000),
+            (1000, 7, 1000, 5),
+            (0, 1, 100, 3),
+            (100, 3, 0, 1),
+        ] {
+            let a_set = random_set(a_len, a_gap);
+            let b_set = random_set(b_len, b_gap);
+            for block_aligned in [false, true] {
+                let a = encode_gb(&a_set, block_aligned);
+                let b = encode_gb(&b_set, block_aligned);
+
+                let expected: Vec<u32> = a_set.intersection(&b_set).copied().collect();
+                let bound = intersection_upper_bound(&a, &b);
+                assert!(bound >= expected.len() && bound <= a_len.min(b_len));
+                let result = intersect(&a, &b, &shuffle_table);
+                assert_eq!(
+                    result.iter_values(&shuffle_table).collect::<Vec<u32>>(),
+                    expected
+                );
+                assert_eq!(result.block_len(), a.block_len());
+
+                let expected: Vec<u32> = a_set.union(&b_set).copied().collect();
+                let result = union(&a, &b, &shuffle_table);
+                assert_eq!(
+                    result.iter_values(&shuffle_table).collect::<Vec<u32>>(),
+                    expected
+                );
+
+                let expected: Vec<u32> = a_set.difference(&b_set).copied().collect();
+                let result = difference(&a, &b, &shuffle_table);
+                assert_eq!(
+                    result.iter_values(&shuffle_table).collect::<Vec<u32>>(),
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_intersection_upper_bound() {
+        let shuffle_table = DescriptorTable::new();
+        let low: BTreeSet<u32> = (1..=1000).collect();
+        let high: BTreeSet<u32> = (2000..3000).collect();
+        let interleaved: BTreeSet<u32> = (0..10).flat_map(|i| i * 300..i * 300 + 10).collect();
+
+        for block_aligned in [false, true] {
+            let low_seq = encode_gb(&low, block_aligned);
+            let high_seq = encode_gb(&high, block_aligned);
+            let interleaved_seq = encode_gb(&interleaved, block_aligned);
+
+            assert_eq!(intersection_upper_bound(&low_seq, &high_seq), 0);
+            assert_eq!(intersection_upper_bound(&high_seq, &low_seq), 0);
+            assert_eq!(intersect(&low_seq, &high_seq, &shuffle_table).len(), 0);
+            assert_eq!(
+                intersection_upper_bound(&low_seq, &encode_gb(&BTreeSet::new(), false)),
+                0
+            );
+
+            let bound = intersection_upper_bound(&low_seq, &interleaved_seq);
+            assert!((40..=100).contains(&bound));
+        }
+
+        //A block that ends on a value the next block repeats
+        let mut fact = VarintGBFactory::new_with_block_len(4);
+        for val in [1, 2, 3, 5, 5, 5, 6, 7] {
+            fact.push_int(val);
+        }
+        let repeated = fact.into_varint_gb();
+        let five = encode_gb(&BTreeSet::from([5]), true);
+        assert!(intersection_upper_bound(&repeated, &five) >= 1);
+        assert_eq!(intersection_upper_bound(&five, &repeated), 1);
+    }
+
+    #[test]
+    fn test_adaptive_intersection() {
+        let shuffle_table = DescriptorTable::new();
+        let intersect_with_stats = |a: &VarintGB<'_>, b: &VarintGB<'_>| {
+            let (result, stats) = intersect_cursors_with_stats(
+                (a.iter_values(&shuffle_table), a.len()),
+                (b.iter_values(&shuffle_table), b.len()),
+                VarintGBFactory::new(),
+            );
+            (
+                result.iter_values(&shuffle_table).collect::<Vec<u32>>(),
+                stats,
+            )
+        };
+
+        //Lists of similar density only zip
+        let a_set: BTreeSet<u32> = (0..2000).map(|val| val * 2).collect();
+        let b_set: BTreeSet<u32> = (0..2000).map(|val| val * 3).collect();
+        let (a, b) = (encode_gb(&a_set, true), encode_gb(&b_set, true));
+        let (result, stats) = intersect_with_stats(&a, &b);
+        assert_eq!(
+            result,
+            a_set.intersection(&b_set).copied().collect::<Vec<u32>>()
+        );
+        assert!(stats.zipper_steps > 0);
+        assert_eq!(stats.gallop_seeks, 0);
+
+        //A short list against a long one only gallops
+        let a_set: BTreeSet<u32> = (0..50).map(|val| val * 997).collect();
+        let b_set: BTreeSet<u32> = (0..50000).collect();
+        let (a, b) = (encode_gb(&a_set, true), encode_gb(&b_set, true));
+        let (result, stats) = intersect_with_stats(&a, &b);
+        assert_eq!(
+            result,
+            a_set.intersection(&b_set).copied().collect::<Vec<u32>>()
+        );
+        assert!(stats.gallop_seeks > 0);
+        assert_eq!(stats.switches, 0);
+
+        //Lists of the same length, where each is dense where the other is sparse
+        let a_set: BTreeSet<u32> = (0..5000)
+            .chain((0..5000).map(|val| 5000 + val * 100))
+            .collect();
+        let b_set: BTreeSet<u32> = (0..5000)
+            .map(|val| val * 100)
+            .chain(500000..505000)
+            .collect();
+        for block_aligned in [false, true] {
+            let (a, b) = (
+                encode_gb(&a_set, block_aligned),
+                encode_gb(&b_set, block_aligned),
+            );
+            let (result, stats) = intersect_with_stats(&a, &b);
+            assert_eq!(
+                result,
+                a_set.intersection(&b_set).copied().collect::<Vec<u32>>()
+            );
+            assert!(stats.switches >= 2);
+            assert!(stats.gallop_seeks > 0 && stats.zipper_steps > 0);
+            //Galloping skips most of the dense regions
+            assert!(stats.zipper_steps + stats.gallop_seeks < a.len() + b.len());
+        }
+    }
+
+    #[test]
+    fn test_mixed_set_ops() {
+        let shuffle_table = DescriptorTable::new();
+        let a_set = random_set(50, 2000);
+        let b_set = random_set(10000, 10);
+        let a = encode_gb(&a_set, true);
+        let mut b_fact = VarintSUFactory::new_with_restarts(64);
+        for val in b_set.iter() {
+            b_fact.push_int(*val);
+        }
+        let b = b_fact.into_varint_su();
+
+        let expected: Vec<u32> = a_set.intersection(&b_set).copied().collect();
+        let result = intersect_cursors(
+            (a.iter_values(&shuffle_table), a.len()),
+            (b.iter(), b.len()),
+            VarintSUFactory::new(),
+        );
+        assert_eq!(result.values().collect::<Vec<u32>>(), expected);
+
+        let expected: Vec<u32> = a_set.union(&b_set).copied().collect();
+        let result = union_cursors(
+            b.iter(),
+            a.iter_values(&shuffle_table),
+            VarintGBFactory::new(),
+        );
+        assert_eq!(
+            result.iter_values(&shuffle_table).collect::<Vec<u32>>(),
+            expected
+        );
+
+        let expected: Vec<u32> = b_set.difference(&a_set).copied().collect();
+        let result = difference_cursors(
+            b.iter(),
+            a.iter_values(&shuffle_table),
+            VarintSUFactory::new(),
+        );
+        assert_eq!(result.values().collect::<Vec<u32>>(), expected);
+    }
+}
@@ -0,0 +1,299 @@
+/*
+The instruction set specific parts of decoding, with a scalar version of each that runs anywhere.
+
+Byte shuffles need SSSE3 on x86_64, which not every x86_64 CPU has, so the backend is detected at
+runtime and stored in the DescriptorTable. CPUs with AVX2 can also shuffle two groups at once, one
+in each 128 bit lane. Lower bounds and prefix sums only use SSE2 or NEON, which every x86_64 and
+aarch64 CPU has, so they are picked at compile time.
+*/
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::{
+    vaddq_u16, vaddvq_u32, vcltq_u32, vdupq_n_u16, vdupq_n_u32, vextq_u16, vld1q_u16, vld1q_u32,
+    vld1q_u8, vqtbl1q_u8, vshrq_n_u32, vst1q_u16, vst1q_u8,
+};
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::{
+    __m128i, __m256i, _mm256_set_m128i, _mm256_shuffle_epi8, _mm256_storeu_si256, _mm_add_epi16,
+    _mm_castsi128_ps, _mm_cmpgt_epi32, _mm_loadu_si128, _mm_movemask_ps, _mm_set1_epi16,
+    _mm_set1_epi32, _mm_shuffle_epi8, _mm_slli_si128, _mm_storeu_si128, _mm_xor_si128,
+};
+
+//The instructions used to shuffle the bytes of a group into place
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Scalar,
+    Ssse3,
+    //SSSE3 for single groups, and AVX2 for pairs of groups
+    Avx2,
+    Neon,
+}
+
+impl Backend {
+    //The fastest backend the CPU we are running on supports
+    pub fn detect() -> Self {
+        [Backend::Avx2, Backend::Ssse3, Backend::Neon]
+            .into_iter()
+            .find(|backend| backend.is_supported())
+            .unwrap_or(Backend::Scalar)
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            Backend::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Ssse3 => is_x86_feature_detected!("ssse3"),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => is_x86_feature_detected!("ssse3") && is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            _ => false,
+        }
+    }
+}
+
+//A shuffle sequence: output byte i is input byte shuffle[i], or 0 if shuffle[i] is 0xFF.
+//Aligned like a vector register, so it can be loaded as one.
+#[repr(C, align(16))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Shuffle(pub [u8; 16]);
+
+//Safety: the backend must be supported by the CPU, as checked by Backend::is_supported
+#[inline(always)]
+pub unsafe fn shuffle_bytes(backend: Backend, bytes: &[u8; 16], shuffle: &Shuffle) -> [u8; 16] {
+    match backend {
+        #[cfg(target_arch = "x86_64")]
+        Backend::Ssse3 | Backend::Avx2 => shuffle_ssse3(bytes, shuffle),
+        #[cfg(target_arch = "aarch64")]
+        Backend::Neon => shuffle_neon(bytes, shuffle),
+        _ => shuffle_scalar(bytes, shuffle),
+    }
+}
+
+//Shuffles two groups, the first into bytes 0-15 and the second into bytes 16-31. AVX2 does both in
+//one instruction, and the other backends shuffle them one at a time.
+//Safety: the backend must be supported by the CPU, as checked by Backend::is_supported
+#[inline(always)]
+pub unsafe fn shuffle_bytes_pair(
+    backend: Backend,
+    bytes: [&[u8; 16]; 2],
+    shuffles: [&Shuffle; 2],
+) -> [u8; 32] {
+    match backend {
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx2 => shuffle_pair_avx2(bytes, shuffles),
+        _ => {
+            let mut output = [0; 32];
+            output[..16].copy_from_slice(&shuffle_bytes(backend, bytes[0], shuffles[0]));
+            output[16..].copy_from_slice(&shuffle_bytes(backend, bytes[1], shuffles[1]));
+            output
+        }
+    }
+}
+
+pub fn shuffle_scalar(bytes: &[u8; 16], shuffle: &Shuffle) -> [u8; 16] {
+    let mut output = [0; 16];
+    for (out, index) in output.iter_mut().zip(shuffle.0) {
+        if index < 16 {
+            *out = bytes[index as usize];
+        }
+    }
+    output
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+unsafe fn shuffle_ssse3(bytes: &[u8; 16], shuffle: &Shuffle) -> [u8; 16] {
+    let mut output = [0; 16];
+    let shuffled = _mm_shuffle_epi8(
+        _mm_loadu_si128(bytes.as_ptr() as *const __m128i),
+        _mm_loadu_si128(shuffle.0.as_ptr() as *const __m128i),
+    );
+    _mm_storeu_si128(output.as_mut_ptr() as *mut __m128i, shuffled);
+    output
+}
+
+//The 256 bit shuffle never moves bytes between the two lanes, so each lane shuffles its own group
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn shuffle_pair_avx2(bytes: [&[u8; 16]; 2], shuffles: [&Shuffle; 2]) -> [u8; 32] {
+    let mut output = [0; 32];
+    let load = |bytes: &[u8; 16]| _mm_loadu_si128(bytes.as_ptr() as *const __m128i);
+    let shuffled = _mm256_shuffle_epi8(
+        _mm256_set_m128i(load(bytes[1]), load(bytes[0])),
+        _mm256_set_m128i(load(&shuffles[1].0), load(&shuffles[0].0)),
+    );
+    _mm256_storeu_si256(output.as_mut_ptr() as *mut __m256i, shuffled);
+    output
+}
+
+//Table lookups with an index of 16 or more give 0, so 0xFF works as it does for SSSE3
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn shuffle_neon(bytes: &[u8; 16], shuffle: &Shuffle) -> [u8; 16] {
+    let mut output = [0; 16];
+    let shuffled = vqtbl1q_u8(vld1q_u8(bytes.as_ptr()), vld1q_u8(shuffle.0.as_ptr()));
+    vst1q_u8(output.as_mut_ptr(), shuffled);
+    output
+}
+
+//Returns the index of the first value >= target in a sorted slice, like partition_point.
+//Compares four values at a time, and counts the lanes that are below the target.
+#[cfg(target_arch = "x86_64")]
+pub fn lower_bound_simd(values: &[u32], target: u32) -> usize {
+    let mut groups = values.chunks_exact(4);
+    let mut index = 0;
+
+    unsafe {
+        //SSE only has signed compares, so the sign bit is flipped on both sides
+        let sign = _mm_set1_epi32(i32::MIN);
+        let target_vec = _mm_xor_si128(_mm_set1_epi32(target as i32), sign);
+
+        for group in groups.by_ref() {
+            let group_vec = _mm_loadu_si128(group.as_ptr() as *const __m128i);
+            let below = _mm_cmpgt_epi32(target_vec, _mm_xor_si128(group_vec, sign));
+            let below_count = _mm_movemask_ps(_mm_castsi128_ps(below)).count_ones() as usize;
+            index += below_count;
+            if below_count < 4 {
+                return index;
+            }
+        }
+    }
+
+    index + groups.remainder().partition_point(|val| *val < target)
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn lower_bound_simd(values: &[u32], target: u32) -> usize {
+    let mut groups = values.chunks_exact(4);
+    let mut index = 0;
+
+    unsafe {
+        let target_vec = vdupq_n_u32(target);
+
+        for group in groups.by_ref() {
+            //Lanes below the target are all ones, so their top bits sum to the count
+            let below = vcltq_u32(vld1q_u32(group.as_ptr()), target_vec);
+            let below_count = vaddvq_u32(vshrq_n_u32::<31>(below)) as usize;
+            index += below_count;
+            if below_count < 4 {
+                return index;
+            }
+        }
+    }
+
+    index + groups.remainder().partition_point(|val| *val < target)
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn lower_bound_simd(values: &[u32], target: u32) -> usize {
+    values.partition_point(|val| *val < target)
+}
+
+//Adds base to a prefix sum of the eight deltas, wrapping around like u16 addition in a register
+#[cfg(target_arch = "x86_64")]
+pub fn prefix_sum_u16(deltas: [u16; 8], base: u16) -> [u16; 8] {
+    let mut output = [0; 8];
+    unsafe {
+        let mut values = _mm_loadu_si128(deltas.as_ptr() as *const __m128i);
+        values = _mm_add_epi16(values, _mm_slli_si128::<2>(values));
+        values = _mm_add_epi16(values, _mm_slli_si128::<4>(values));
+        values = _mm_add_epi16(values, _mm_slli_si128::<8>(values));
+        values = _mm_add_epi16(values, _mm_set1_epi16(base as i16));
+        _mm_storeu_si128(output.as_mut_ptr() as *mut __m128i, values);
+    }
+    output
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn prefix_sum_u16(deltas: [u16; 8], base: u16) -> [u16; 8] {
+    let mut output = [0; 8];
+    unsafe {
+        //Shifting in zero lanes from the left, like the byte shifts on x86_64
+        let zero = vdupq_n_u16(0);
+        let mut values = vld1q_u16(deltas.as_ptr());
+        values = vaddq_u16(values, vextq_u16::<7>(zero, values));
+        values = vaddq_u16(values, vextq_u16::<6>(zero, values));
+        values = vaddq_u16(values, vextq_u16::<4>(zero, values));
+        values = vaddq_u16(values, vdupq_n_u16(base));
+        vst1q_u16(output.as_mut_ptr(), values);
+    }
+    output
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn prefix_sum_u16(deltas: [u16; 8], base: u16) -> [u16; 8] {
+    let mut output = [0; 8];
+    let mut last = base;
+    for (out, delta) in output.iter_mut().zip(deltas) {
+        last = last.wrapping_add(delta);
+        *out = last;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::{
+        prefix_sum_u16, shuffle_bytes, shuffle_bytes_pair, shuffle_scalar, Backend, Shuffle,
+    };
+
+    #[test]
+    fn test_backends_agree() {
+        assert!(Backend::Scalar.is_supported());
+        assert!(Backend::detect().is_supported());
+
+        let mut rng = rand::thread_rng();
+        let mut random_shuffle = || {
+            let mut shuffle = Shuffle([0; 16]);
+            for index in shuffle.0.iter_mut() {
+                *index = if rng.gen_range(0..4) == 0 {
+                    0xFF
+                } else {
+                    rng.gen_range(0..16)
+                };
+            }
+            shuffle
+        };
+
+        for _ in 0..1000 {
+            let shuffles = [random_shuffle(), random_shuffle()];
+            let bytes: [[u8; 16]; 2] = rand::random();
+
+            let expected = [0, 1].map(|i| shuffle_scalar(&bytes[i], &shuffles[i]));
+            for backend in [
+                Backend::Scalar,
+                Backend::Ssse3,
+                Backend::Avx2,
+                Backend::Neon,
+            ] {
+                if backend.is_supported() {
+                    assert_eq!(
+                        unsafe { shuffle_bytes(backend, &bytes[0], &shuffles[0]) },
+                        expected[0]
+                    );
+                    let pair = unsafe {
+                        shuffle_bytes_pair(
+                            backend,
+                            [&bytes[0], &bytes[1]],
+                            [&shuffles[0], &shuffles[1]],
+                        )
+                    };
+                    assert_eq!(pair[..16], expected[0]);
+                    assert_eq!(pair[16..], expected[1]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_prefix_sum_u16() {
+        assert_eq!(
+            prefix_sum_u16([1, 2, 3, 4, 5, 6, 7, 8], 100),
+            [101, 103, 106, 110, 115, 121, 128, 136]
+        );
+        assert_eq!(prefix_sum_u16([1, 0, 0, 0, 0, 0, 0, 0], u16::MAX), [0; 8]);
+    }
+}
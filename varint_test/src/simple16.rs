@@ -0,0 +1,241 @@
+/*
+This refers to an implementation of a compressed integer sequence, with gaps packed into 32 bit words by Simple-16
+
+Every word holds a 4 bit selector, and 28 bits of data:
+
+[Selector] [Data]
+
+The selector picks one of 16 ways to split the 28 data bits into fields of equal or mixed width.
+The first gap is stored in the lowest bits. Gaps must be below 2^28.
+
+Unlike Simple-8b's 64 bit words, a partly filled final word wastes at most 28 bits, which matters
+for lists of only a few values.
+*/
+
+//(Number of fields, bits per field) for each selector
+const SELECTORS: [&[(u32, u32)]; 16] = [
+    &[(28, 1)],
+    &[(7, 2), (14, 1)],
+    &[(7, 1), (7, 2), (7, 1)],
+    &[(14, 1), (7, 2)],
+    &[(14, 2)],
+    &[(1, 4), (8, 3)],
+    &[(1, 3), (4, 4), (3, 3)],
+    &[(7, 4)],
+    &[(4, 5), (2, 4)],
+    &[(2, 4), (4, 5)],
+    &[(3, 6), (2, 5)],
+    &[(2, 5), (3, 6)],
+    &[(4, 7)],
+    &[(1, 10), (2, 9)],
+    &[(2, 14)],
+    &[(1, 28)],
+];
+
+const MAX_GAP: u32 = (1 << 28) - 1;
+
+pub struct Simple16 {
+    pub words: Box<[u32]>,
+    len: u32,
+}
+
+impl Simple16 {
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.words.len() * 4
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            words: &self.words,
+            word_index: 0,
+            buffer: [0; 28],
+            buffer_len: 0,
+            buffer_index: 0,
+            remaining: self.len,
+            last_value: 0,
+        }
+    }
+}
+
+pub struct Iter<'a> {
+    words: &'a [u32],
+    word_index: usize,
+    buffer: [u32; 28],
+    buffer_len: usize,
+    buffer_index: usize,
+    remaining: u32,
+    last_value: u32,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        if self.buffer_index == self.buffer_len {
+            self.buffer_len = unpack_word(self.words[self.word_index], &mut self.buffer);
+            self.buffer_index = 0;
+            self.word_index += 1;
+        }
+
+        self.last_value += self.buffer[self.buffer_index];
+        self.buffer_index += 1;
+        self.remaining -= 1;
+        Some(self.last_value)
+    }
+}
+
+//Unpacks the fields of a word into output, and returns the number of fields
+fn unpack_word(word: u32, output: &mut [u32; 28]) -> usize {
+    let mut data = word & MAX_GAP;
+    let mut count = 0;
+    for (fields, bits) in SELECTORS[(word >> 28) as usize] {
+        let mask = (1 << bits) - 1;
+        for _ in 0..*fields {
+            output[count] = data & mask;
+            data >>= bits;
+            count += 1;
+        }
+    }
+    count
+}
+
+//Packs as many of the gaps as possible into one word, using the first selector that fits them.
+//Returns the word and the number of gaps it holds. If there are fewer gaps than a selector has
+//fields, the remaining fields are left as 0.
+fn pack_word(gaps: &[u32]) -> (u32, usize) {
+    for (selector, layout) in SELECTORS.iter().enumerate() {
+        let mut word = 0;
+        let mut shift = 0;
+        let mut packed = 0;
+        let mut fits = true;
+
+        'fields: for (fields, bits) in layout.iter() {
+            for _ in 0..*fields {
+                if packed == gaps.len() {
+                    break 'fields;
+                }
+                if gaps[packed] >> bits != 0 {
+                    fits = false;
+                    break 'fields;
+                }
+                word |= gaps[packed] << shift;
+                shift += bits;
+                packed += 1;
+            }
+        }
+
+        if fits {
+            return ((selector as u32) << 28 | word, packed);
+        }
+    }
+    unreachable!("gaps are checked to be below 2^28 when pushed")
+}
+
+pub struct Simple16Factory {
+    words: Vec<u32>,
+    pending: Vec<u32>,
+    top: u32,
+    len: u32,
+}
+
+impl Simple16Factory {
+    pub fn new() -> Self {
+        Simple16Factory {
+            words: Vec::new(),
+            pending: Vec::with_capacity(28),
+            top: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push_int(&mut self, x: u32) {
+        let gap = x - self.top;
+        assert!(gap <= MAX_GAP, "Simple-16 gaps must be below 2^28");
+        self.top = x;
+        self.len += 1;
+        self.pending.push(gap);
+
+        //Once 28 gaps are pending, every selector has enough gaps to choose from
+        if self.pending.len() == 28 {
+            self.flush_word();
+        }
+    }
+
+    fn flush_word(&mut self) {
+        let (word, packed) = pack_word(&self.pending);
+        self.words.push(word);
+        self.pending.drain(..packed);
+    }
+
+    pub fn into_simple16(mut self) -> Simple16 {
+        while !self.pending.is_empty() {
+            self.flush_word();
+        }
+
+        Simple16 {
+            words: self.words.into_boxed_slice(),
+            len: self.len,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::{pack_word, unpack_word, Simple16Factory, MAX_GAP};
+
+    #[test]
+    fn test_pack_word() {
+        //Three 9-bit and one 10-bit value do not fit selector 12 (4 x 7), but fit 13 (1 x 10, 2 x 9)
+        let (word, packed) = pack_word(&[1000, 300, 200, 1]);
+        assert_eq!(word >> 28, 13);
+        assert_eq!(packed, 3);
+
+        let mut output = [0; 28];
+        assert_eq!(unpack_word(word, &mut output), 3);
+        assert_eq!(output[..3], [1000, 300, 200]);
+    }
+
+    #[test]
+    fn test_compressions() {
+        let mut rng = rand::thread_rng();
+
+        for len in [0, 1, 3, 7, 28, 29, 1000] {
+            let mut reference_vector = Vec::new();
+            let mut value = 0;
+            for _ in 0..len {
+                //Mostly small gaps, with the occasional large one
+                value += if rng.gen_range(0..10) == 0 {
+                    rng.gen_range(1..1 << 20)
+                } else {
+                    rng.gen_range(1..20)
+                };
+                reference_vector.push(value);
+            }
+
+            let mut fact = Simple16Factory::new();
+            for val in reference_vector.iter() {
+                fact.push_int(*val);
+            }
+            let seq = fact.into_simple16();
+
+            assert_eq!(seq.len(), len);
+            assert_eq!(seq.iter().collect::<Vec<u32>>(), reference_vector);
+        }
+
+        let mut fact = Simple16Factory::new();
+        fact.push_int(MAX_GAP);
+        fact.push_int(MAX_GAP + 1);
+        let seq = fact.into_simple16();
+        assert_eq!(seq.iter().collect::<Vec<u32>>(), [MAX_GAP, MAX_GAP + 1]);
+    }
+}
@@ -0,0 +1,221 @@
+/*
+Rewrites lists in another codec, for the transcode subcommand of the binary, so codecs can be
+compared on identical data.
+
+Besides the formats of the bench module, lists can be read from and written to a file of
+serialized sequences. Every sequence is written by to_bytes, in a length-prefixed frame:
+
+[Length (u32, little endian)] [Serialized sequence]
+
+Only GB and SU sequences can be serialized. Every list is encoded with the target codec and
+decoded again, and the values are compared against the input.
+*/
+
+use std::io::{self, Write};
+
+#[cfg(feature = "ef")]
+use crate::elias_fano::EliasFanoFactory;
+#[cfg(feature = "s16")]
+use crate::simple16::Simple16Factory;
+#[cfg(feature = "gb")]
+use crate::varint_gb::{DescriptorTable, VarintGB, VarintGBFactory};
+#[cfg(feature = "su")]
+use crate::varint_su::{VarintSU, VarintSUFactory};
+use crate::{
+    bench::Codec,
+    codec::{encode, IntSequenceCodec, IntSequenceFactory},
+    error::CodecError,
+    frame::FrameReader,
+};
+
+#[derive(Copy, Clone, Debug)]
+pub struct TranscodeResult {
+    pub codec: Codec,
+    pub lists: usize,
+    pub integers: usize,
+    pub bytes: usize,
+    //Lists that did not decode to the input values
+    pub mismatched_lists: usize,
+}
+
+impl TranscodeResult {
+    pub fn bits_per_integer(&self) -> f64 {
+        self.bytes as f64 * 8.0 / self.integers as f64
+    }
+}
+
+#[allow(unreachable_patterns)]
+pub fn is_serializable(codec: Codec) -> bool {
+    match codec {
+        #[cfg(feature = "gb")]
+        Codec::Gb | Codec::GbBlocks | Codec::GbRuns => true,
+        #[cfg(feature = "su")]
+        Codec::Su => true,
+        _ => false,
+    }
+}
+
+//Encodes every list with the codec, and checks that it decodes to the same values
+pub fn transcode(codec: Codec, lists: &[Vec<u32>]) -> TranscodeResult {
+    match codec {
+        #[cfg(feature = "gb")]
+        Codec::Gb => transcode_with(codec, VarintGBFactory::new, lists),
+        #[cfg(feature = "gb")]
+        Codec::GbBlocks => transcode_with(codec, VarintGBFactory::new_block_aligned, lists),
+        #[cfg(feature = "gb")]
+        Codec::GbRuns => transcode_with(codec, VarintGBFactory::new_with_run_escapes, lists),
+        #[cfg(feature = "su")]
+        Codec::Su => transcode_with(codec, VarintSUFactory::new, lists),
+        #[cfg(feature = "s16")]
+        Codec::Simple16 => transcode_with(codec, Simple16Factory::new, lists),
+        #[cfg(feature = "ef")]
+        Codec::EliasFano => transcode_with(codec, EliasFanoFactory::new, lists),
+    }
+}
+
+fn transcode_with<F: IntSequenceFactory>(
+    codec: Codec,
+    make_factory: impl Fn() -> F,
+    lists: &[Vec<u32>],
+) -> TranscodeResult {
+    let mut bytes = 0;
+    let mut mismatched_lists = 0;
+    for list in lists.iter() {
+        let seq = encode(make_factory(), list);
+        bytes += seq.size_in_bytes();
+        if !seq.values().eq(list.iter().copied()) {
+            mismatched_lists += 1;
+        }
+    }
+
+    TranscodeResult {
+        codec,
+        lists: lists.len(),
+        integers: lists.iter().map(|list| list.len()).sum(),
+        bytes,
+        mismatched_lists,
+    }
+}
+
+//Reads a file of serialized sequences of the codec
+pub fn read_serialized(codec: Codec, bytes: &[u8]) -> io::Result<Vec<Vec<u32>>> {
+    check_serializable(codec)?;
+    parse_serialized(codec, bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn parse_serialized(codec: Codec, bytes: &[u8]) -> Result<Vec<Vec<u32>>, CodecError> {
+    let mut reader = FrameReader::new(bytes);
+    let mut lists = Vec::new();
+    while reader.remaining() > 0 {
+        let len = reader.read_u32()? as usize;
+        let frame = reader.read_bytes(len)?;
+        lists.push(deserialize(codec, frame)?);
+    }
+    Ok(lists)
+}
+
+#[allow(unreachable_patterns, unused_variables)]
+fn deserialize(codec: Codec, frame: &[u8]) -> Result<Vec<u32>, CodecError> {
+    match codec {
+        #[cfg(feature = "su")]
+        Codec::Su => Ok(VarintSU::from_slice(frame)?.values().collect()),
+        #[cfg(feature = "gb")]
+        Codec::Gb | Codec::GbBlocks | Codec::GbRuns => Ok(VarintGB::from_slice(frame)?
+            .iter_values(DescriptorTable::global())
+            .collect()),
+        _ => unreachable!("{codec} sequences cannot be serialized"),
+    }
+}
+
+//Writes the lists as a file of serialized sequences of the codec, and returns its length
+pub fn write_serialized<W: Write>(
+    codec: Codec,
+    lists: &[Vec<u32>],
+    writer: &mut W,
+) -> io::Result<usize> {
+    check_serializable(codec)?;
+
+    let mut written = 0;
+    for list in lists.iter() {
+        let bytes = serialize(codec, list);
+        writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        writer.write_all(&bytes)?;
+        written += 4 + bytes.len();
+    }
+    Ok(written)
+}
+
+#[allow(unreachable_patterns, unused_variables)]
+fn serialize(codec: Codec, list: &[u32]) -> Vec<u8> {
+    match codec {
+        #[cfg(feature = "gb")]
+        Codec::Gb => encode(VarintGBFactory::new(), list).to_bytes(),
+        #[cfg(feature = "gb")]
+        Codec::GbBlocks => encode(VarintGBFactory::new_block_aligned(), list).to_bytes(),
+        #[cfg(feature = "gb")]
+        Codec::GbRuns => encode(VarintGBFactory::new_with_run_escapes(), list).to_bytes(),
+        #[cfg(feature = "su")]
+        Codec::Su => encode(VarintSUFactory::new(), list).to_bytes(),
+        _ => unreachable!("{codec} sequences cannot be serialized"),
+    }
+}
+
+fn check_serializable(codec: Codec) -> io::Result<()> {
+    if is_serializable(codec) {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{codec} sequences cannot be serialized"),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::bench::Codec;
+
+    use super::{is_serializable, read_serialized, transcode, write_serialized};
+
+    #[test]
+    fn test_serialized_lists() {
+        let lists: Vec<Vec<u32>> = vec![(1..=1000).map(|val| val * 7).collect(), vec![], vec![3]];
+
+        for &codec in Codec::ALL {
+            let mut bytes = Vec::new();
+            let written = write_serialized(codec, &lists, &mut bytes);
+            if !is_serializable(codec) {
+                assert_eq!(written.unwrap_err().kind(), io::ErrorKind::Unsupported);
+                assert!(read_serialized(codec, &bytes).is_err());
+                continue;
+            }
+
+            assert_eq!(written.unwrap(), bytes.len());
+            assert_eq!(read_serialized(codec, &bytes).unwrap(), lists);
+            let err = read_serialized(codec, &bytes[..bytes.len() - 1]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+
+        //GB and SU sequences are not interchangeable
+        #[cfg(all(feature = "gb", feature = "su"))]
+        {
+            let mut bytes = Vec::new();
+            write_serialized(Codec::Su, &lists, &mut bytes).unwrap();
+            assert!(read_serialized(Codec::Gb, &bytes).is_err());
+        }
+    }
+
+    #[test]
+    fn test_transcode() {
+        let lists = vec![(1..=1000).map(|val| val * 3).collect(), vec![7], vec![]];
+        for &codec in Codec::ALL {
+            let result = transcode(codec, &lists);
+            assert_eq!(result.lists, 3);
+            assert_eq!(result.integers, 1001);
+            assert_eq!(result.mismatched_lists, 0);
+            assert!(result.bits_per_integer() > 0.0);
+        }
+    }
+}
@@ -1,9 +1,18 @@
-use std::arch::x86_64::_mm_loadu_si128;
 use std::{
-    arch::x86_64::{__m128i, _mm_shuffle_epi8},
+    borrow::Cow,
+    io::{self, Read, Write},
+    ops::Range,
     ptr,
+    sync::{Arc, OnceLock},
 };
 
+use crate::codec::{CursorMark, GapMode};
+use crate::error::CodecError;
+use crate::frame::FrameReader;
+use crate::layout::shuffle_for_lengths;
+pub use crate::simd::lower_bound_simd;
+use crate::simd::{prefix_sum_u16, shuffle_bytes, shuffle_bytes_pair, Backend, Shuffle};
+
 /*
 This refers to an implementation of a compressed integer sequence, with integer lengths described in Grouped Binary
 
@@ -15,19 +24,92 @@ The descriptor byte stores the size of each value in 1-4 byte:
 [00-01-10-11]
 [Bytes in value 4 - Bytes in value 3 - Bytes in value 2 - Bytes in value 1]
 
-We use SIMD functions to decode.
+We use SIMD functions to decode, through the backend picked by the DescriptorTable.
+
+Factories from new_with_run_escapes write a run of at least MIN_ESCAPED_RUN consecutive values that
+starts at a group boundary as one escape group, instead of a group for every four gaps of 1:
+
+[Descriptor 0] [0] [Last value of the run (u32)]
+
+No other group in such a sequence starts with descriptor 0 and a byte of 0, as a first gap of 0
+is written in two bytes. Escaped runs are whole groups. The chunk and value iterators expand them,
+and next_geq skips them without expanding them. The other decode paths fall back to the value
+iterator for sequences with escapes.
 */
 
-pub struct VarintGB {
-    pub byte_stream: Box<[u8]>,
+//The byte stream is borrowed when the sequence is loaded in place with from_slice, e.g. from a
+//memory mapped file, and owned otherwise
+pub struct VarintGB<'a> {
+    pub byte_stream: Cow<'a, [u8]>,
     len: u32,
+    top: u32,
+    stats: GapStats,
+    blocks: Box<[BlockHeader]>,
+    block_len: u32,
+    //NonStrict or Absolute. Gaps are stored as they are, so there is no use for Strict.
+    gap_mode: GapMode,
+    //Long runs of consecutive values may be written as escape groups
+    run_escapes: bool,
+}
+
+//Default number of values in a block, when the factory is block aligned
+pub const BLOCK_LEN: usize = 128;
+
+//The shortest run of consecutive values that new_with_run_escapes writes as an escape group
+pub const MIN_ESCAPED_RUN: u32 = 64;
+
+//Bytes in an escape group
+const ESCAPE_LEN: usize = 6;
+
+//The last value of the run, if the group at index is a whole escape group
+fn escape_end(byte_stream: &[u8], index: usize) -> Option<u32> {
+    if byte_stream[index] != 0 || byte_stream.get(index + 1) != Some(&0) {
+        return None;
+    }
+    let end = byte_stream.get(index + 2..index + ESCAPE_LEN)?;
+    Some(u32::from_le_bytes(end.try_into().unwrap()))
+}
+
+//Picks the number of values per block for a list of the given length, as sqrt(len) rounded
+//up to whole groups. Fewer, longer blocks make the headers smaller, but each block costs more to
+//decode when skipping to it.
+pub fn auto_block_len(len: usize) -> usize {
+    ((len as f64).sqrt() as usize)
+        .next_multiple_of(4)
+        .clamp(16, 1 << 16)
+}
+
+//Header of a block of values. The block starts with a descriptor at byte_offset,
+//and its values are deltas from the last value of the previous block.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BlockHeader {
+    pub last_value: u32,
+    pub byte_offset: u32,
+    pub byte_len: u32,
+}
+
+//Statistics about the gaps, collected by the factory while values are pushed
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GapStats {
+    pub max_gap: u32,
+    //Number of maximal runs of consecutive values, i.e. runs of gaps equal to 1
+    pub gap_one_runs: u32,
+    pub longest_gap_one_run: u32,
+    //Bytes spent on values encoded in 1, 2, 3 and 4 bytes
+    pub bytes_per_length: [u32; 4],
 }
 
-impl VarintGB {
+impl VarintGB<'_> {
     pub fn new() -> Self {
         VarintGB {
-            byte_stream: Vec::new().into_boxed_slice(),
+            byte_stream: Cow::Owned(Vec::new()),
             len: 0,
+            top: 0,
+            stats: GapStats::default(),
+            blocks: Vec::new().into_boxed_slice(),
+            block_len: 0,
+            gap_mode: GapMode::NonStrict,
+            run_escapes: false,
         }
     }
 
@@ -38,10 +120,50 @@ impl VarintGB {
             descriptor_index: 0,
             last_top: 0,
             len: self.len,
+            blocks: &self.blocks,
+            block_len: self.block_len,
+            value_index: 0,
+            chunk: [0; 4],
+            chunk_pos: 0,
+            chunk_len: 0,
+            absolute: self.gap_mode == GapMode::Absolute,
+            run_escapes: self.run_escapes,
+        }
+    }
+
+    //Iterates the values one at a time, stopping exactly after the last one
+    pub fn iter_values<'a, 'b>(&'a self, shuffle_table: &'b DescriptorTable) -> ValueIter<'a, 'b> {
+        ValueIter {
+            chunks: self.iter(shuffle_table),
+            chunk: [0; 4],
+            chunk_pos: 4,
+            chunk_offset: 0,
+            chunk_base: 0,
+            remaining: self.len(),
+        }
+    }
+
+    //Iterates the sequence as u16 values, eight at a time, if the largest value fits in a u16 and
+    //the sequence has no escaped runs
+    pub fn iter_u16<'a, 'b>(
+        &'a self,
+        shuffle_table: &'b DescriptorTable,
+    ) -> Option<IterU16<'a, 'b>> {
+        if !self.fits_u16() || self.run_escapes {
+            return None;
         }
+
+        Some(IterU16 {
+            descriptor_table: shuffle_table,
+            byte_stream: &self.byte_stream,
+            descriptor_index: 0,
+            last_top: 0,
+            absolute: self.gap_mode == GapMode::Absolute,
+        })
     }
 
-    pub fn iter_unsafe<'a, 'b>(&'a self, shuffle_table: &'b DescriptorTable) -> IterUnsafe<'b> {
+    pub fn iter_unsafe<'b>(&self, shuffle_table: &'b DescriptorTable) -> IterUnsafe<'b> {
+        assert!(!self.run_escapes, "iter_unsafe cannot expand escaped runs");
         IterUnsafe {
             descriptor_table: shuffle_table,
             src: ptr::addr_of!(self.byte_stream[0]) as *mut u8,
@@ -49,42 +171,902 @@ impl VarintGB {
         }
     }
 
-    #[allow(dead_code)]
     pub fn get_values(&self, descriptor_table: &DescriptorTable) -> Vec<u32> {
-        let mut output = Vec::with_capacity(self.len());
+        if self.run_escapes {
+            return self.iter_values(descriptor_table).collect();
+        }
+
+        let mut output = Vec::with_capacity(self.len() + 4);
+        let mut last = 0;
         let mut descriptor_index = 0;
         while descriptor_index < self.byte_stream.len() {
             let descriptor = self.byte_stream[descriptor_index];
             let desc_entry = descriptor_table.get_entry_for_descriptor(descriptor);
 
-            if descriptor_index + 17 >= self.byte_stream.len() {
+            let mut chunk = if descriptor_index + 17 >= self.byte_stream.len() {
                 let chunk_byte_stream = &self.byte_stream[descriptor_index + 1..];
-                descriptor_index += (desc_entry.length + 1) as usize;
-                let delta_chunk = decode_chunk_safe_non_simd(descriptor, chunk_byte_stream);
-
-                for val in delta_chunk {
-                    if val == 0 {
-                        return output;
-                    }
-                    output.push(val + output.last().unwrap_or(&0));
-                }
+                decode_chunk_safe_non_simd(descriptor, chunk_byte_stream)
+            } else {
+                let chunk_addr = ptr::addr_of!(self.byte_stream[descriptor_index + 1]);
+                descriptor_table.decode_chunk_by_address(chunk_addr, &desc_entry.shuffle_sequence)
+            };
+            self.chunk_to_values(&mut chunk, last);
+            last = chunk[3];
+            output.extend_from_slice(&chunk);
+            descriptor_index += (desc_entry.length + 1) as usize;
+        }
 
-                continue;
+        //The final group is padded, or cut off after its last value, so the length decides where
+        //the values end. Gaps of 0 are values like any other.
+        output.truncate(self.len());
+        output
+    }
+
+    //Decodes the whole sequence like get_values, but detects runs of identical descriptors.
+    //Chunks in a run share the same shuffle sequence and length, so the table is only consulted
+    //once per run, and the run is decoded in a tight loop with a constant shuffle and stride.
+    pub fn get_values_run_aware(&self, descriptor_table: &DescriptorTable) -> Vec<u32> {
+        let mut output = Vec::with_capacity(self.len() + 4);
+        self.decode_run_aware_into(descriptor_table, &mut output);
+        output
+    }
+
+    //Appends the values to output, like get_values_run_aware. Only allocates if output does not
+    //have room for len + 4 more values.
+    pub fn decode_run_aware_into(&self, descriptor_table: &DescriptorTable, output: &mut Vec<u32>) {
+        if self.run_escapes {
+            output.extend(self.iter_values(descriptor_table));
+            return;
+        }
+
+        let start = output.len();
+        output.reserve(self.len() + 4);
+        let mut last = 0;
+        let mut descriptor_index = 0;
+
+        while descriptor_index < self.byte_stream.len() {
+            let descriptor = self.byte_stream[descriptor_index];
+            let desc_entry = descriptor_table.get_entry_for_descriptor(descriptor);
+            let shuffle_sequence = desc_entry.shuffle_sequence;
+            let stride = (desc_entry.length + 1) as usize;
+
+            //Constant-shuffle loop, as long as the descriptor repeats and 16 bytes can be loaded
+            while descriptor_index + 17 < self.byte_stream.len()
+                && self.byte_stream[descriptor_index] == descriptor
+            {
+                let chunk_addr = ptr::addr_of!(self.byte_stream[descriptor_index + 1]);
+                let mut chunk =
+                    descriptor_table.decode_chunk_by_address(chunk_addr, &shuffle_sequence);
+                self.chunk_to_values(&mut chunk, last);
+                last = chunk[3];
+                output.extend_from_slice(&chunk);
+                descriptor_index += stride;
             }
 
-            let chunk_addr = ptr::addr_of!(self.byte_stream[descriptor_index + 1]) as *mut __m128i;
-            let delta_chunk = decode_chunk_by_address(chunk_addr, desc_entry.shuffle_sequence);
-            for val in delta_chunk {
-                output.push(val + output.last().unwrap_or(&0));
+            //The tail of the stream is decoded safely, one chunk at a time
+            if descriptor_index + 17 >= self.byte_stream.len()
+                && descriptor_index < self.byte_stream.len()
+            {
+                let descriptor = self.byte_stream[descriptor_index];
+                let length = descriptor_table.get_entry_for_descriptor(descriptor).length;
+                let chunk_byte_stream = &self.byte_stream[descriptor_index + 1..];
+                let mut chunk = decode_chunk_safe_non_simd(descriptor, chunk_byte_stream);
+                self.chunk_to_values(&mut chunk, last);
+                last = chunk[3];
+                output.extend_from_slice(&chunk);
+                descriptor_index += (length + 1) as usize;
             }
-            descriptor_index += (desc_entry.length + 1) as usize;
         }
-        return output;
+
+        output.truncate(start + self.len());
+    }
+
+    pub fn get_values_u16(&self, descriptor_table: &DescriptorTable) -> Option<Vec<u16>> {
+        let mut output: Vec<u16> = self.iter_u16(descriptor_table)?.flatten().collect();
+        output.truncate(self.len());
+        Some(output)
     }
 
     pub fn len(&self) -> usize {
         self.len as usize
     }
+
+    //The block headers, if the sequence was built by a block aligned factory
+    pub fn blocks(&self) -> &[BlockHeader] {
+        &self.blocks
+    }
+
+    //The number of values per block, or 0 if the sequence is not block aligned
+    pub fn block_len(&self) -> usize {
+        self.block_len as usize
+    }
+
+    //The first block that may contain a value >= target, found from the headers alone
+    pub fn block_for_value(&self, target: u32) -> Option<usize> {
+        let block = self
+            .blocks
+            .partition_point(|header| header.last_value < target);
+        if block < self.blocks.len() {
+            Some(block)
+        } else {
+            None
+        }
+    }
+
+    //Decodes only the values of one block, using its header to find it in the byte stream
+    pub fn decode_block(&self, block: usize, descriptor_table: &DescriptorTable) -> Vec<u32> {
+        let mut output = Vec::with_capacity(self.block_len() + 4);
+        self.decode_block_into(block, descriptor_table, &mut output);
+        output
+    }
+
+    //Appends the values of one block to output
+    pub fn decode_block_into(
+        &self,
+        block: usize,
+        descriptor_table: &DescriptorTable,
+        output: &mut Vec<u32>,
+    ) {
+        let header = self.blocks[block];
+        let mut last = if block == 0 {
+            0
+        } else {
+            self.blocks[block - 1].last_value
+        };
+
+        let block_len = self.block_len();
+        let start = output.len();
+        let mut descriptor_index = header.byte_offset as usize;
+        let block_end = descriptor_index + header.byte_len as usize;
+        while descriptor_index < block_end {
+            let descriptor = self.byte_stream[descriptor_index];
+            let desc_entry = descriptor_table.get_entry_for_descriptor(descriptor);
+
+            //The load may read into the next block, whose bytes the shuffle ignores
+            let mut chunk = if descriptor_index + 17 <= self.byte_stream.len() {
+                let chunk_addr = ptr::addr_of!(self.byte_stream[descriptor_index + 1]);
+                descriptor_table.decode_chunk_by_address(chunk_addr, &desc_entry.shuffle_sequence)
+            } else {
+                decode_chunk_safe_non_simd(
+                    descriptor,
+                    &self.byte_stream[descriptor_index + 1..block_end],
+                )
+            };
+            self.chunk_to_values(&mut chunk, last);
+            last = chunk[3];
+            output.extend_from_slice(&chunk);
+            descriptor_index += (desc_entry.length + 1) as usize;
+        }
+
+        output.truncate(start + block_len.min(self.len() - block * block_len));
+    }
+
+    //Appends the values v with lo <= v <= hi to output. For block aligned sequences, decoding
+    //starts at the first block that may hold lo, and stops at the block that passes hi.
+    pub fn decode_window(
+        &self,
+        lo: u32,
+        hi: u32,
+        descriptor_table: &DescriptorTable,
+        output: &mut Vec<u32>,
+    ) {
+        assert!(
+            self.gap_mode != GapMode::Absolute,
+            "absolute values are not sorted"
+        );
+        if lo > hi {
+            return;
+        }
+        if self.blocks.is_empty() {
+            for val in self.iter_values(descriptor_table) {
+                if val > hi {
+                    return;
+                }
+                if val >= lo {
+                    output.push(val);
+                }
+            }
+            return;
+        }
+
+        let Some(first_block) = self.block_for_value(lo) else {
+            return;
+        };
+        for block in first_block..self.blocks.len() {
+            let start = output.len();
+            self.decode_block_into(block, descriptor_table, output);
+
+            //Keep only the values inside the window
+            let block_values = &output[start..];
+            let from = lower_bound_simd(block_values, lo);
+            let to = if hi == u32::MAX {
+                block_values.len()
+            } else {
+                lower_bound_simd(block_values, hi + 1)
+            };
+            output.truncate(start + to);
+            output.drain(start..start + from);
+
+            if self.blocks[block].last_value > hi {
+                return;
+            }
+        }
+    }
+
+    //The values v with from_value <= v <= to_value, as a new sequence with the same block length.
+    //The group holding the first value is found by seeking, and its values in the window are
+    //encoded again, as the first value is now stored from 0. The gaps after it are copied byte for
+    //byte up to the first value past to_value, and only the descriptors are rebuilt, as the window
+    //rarely starts at a group boundary. Sequences with escaped runs are copied value by value.
+    pub fn copy_range_compressed(
+        &self,
+        from_value: u32,
+        to_value: u32,
+        descriptor_table: &DescriptorTable,
+    ) -> VarintGB<'static> {
+        assert!(
+            self.gap_mode != GapMode::Absolute,
+            "absolute values are not sorted"
+        );
+        if self.run_escapes {
+            let mut factory = VarintGBFactory::new_with_run_escapes();
+            let mut iter = self.iter_values(descriptor_table);
+            let first = iter.next_geq(from_value);
+            for val in first.into_iter().chain(iter) {
+                if val > to_value {
+                    break;
+                }
+                factory.push_int(val);
+            }
+            return factory.into_varint_gb();
+        }
+
+        let mut factory = if self.blocks.is_empty() {
+            VarintGBFactory::new()
+        } else {
+            VarintGBFactory::new_with_block_len(self.block_len())
+        };
+        let mut chunks = self.iter(descriptor_table);
+        if from_value > to_value || !chunks.skip_blocks(from_value) {
+            return factory.into_varint_gb();
+        }
+
+        //The first boundary group
+        loop {
+            let Some(chunk) = chunks.next() else {
+                return factory.into_varint_gb();
+            };
+            let chunk_len = (self.len - (chunks.value_index - 4)).min(4) as usize;
+            let Some(pos) = chunk[..chunk_len].iter().position(|val| *val >= from_value) else {
+                continue;
+            };
+            for val in chunk[pos..chunk_len].iter() {
+                if *val > to_value {
+                    return factory.into_varint_gb();
+                }
+                factory.push_int(*val);
+            }
+            break;
+        }
+
+        //The groups after it, up to and including the second boundary group
+        let mut descriptor_index = chunks.descriptor_index;
+        let mut value_index = chunks.value_index;
+        while value_index < self.len {
+            let descriptor = self.byte_stream[descriptor_index];
+            let mut offset = descriptor_index + 1;
+            for i in 0..(self.len - value_index).min(4) as usize {
+                let gap_bytes =
+                    &self.byte_stream[offset..offset + descriptor_length_i(descriptor, i) as usize];
+                if factory.get_top() + gap_from_bytes(gap_bytes) > to_value {
+                    return factory.into_varint_gb();
+                }
+                factory.push_gap_bytes(gap_bytes);
+                offset += gap_bytes.len();
+            }
+            value_index += 4;
+            descriptor_index += 1 + descriptor_length_total(descriptor) as usize;
+        }
+        factory.into_varint_gb()
+    }
+
+    //The value at the given index, or None if it is past the end
+    pub fn get(&self, index: usize, descriptor_table: &DescriptorTable) -> Option<u32> {
+        if index >= self.len() {
+            return None;
+        }
+        self.iter_from_index(index, descriptor_table).next()
+    }
+
+    //The values at the indices in range. The range is cut off at the end of the sequence.
+    pub fn get_range(&self, range: Range<usize>, descriptor_table: &DescriptorTable) -> Vec<u32> {
+        let end = range.end.min(self.len());
+        if range.start >= end {
+            return Vec::new();
+        }
+        self.iter_from_index(range.start, descriptor_table)
+            .take(end - range.start)
+            .collect()
+    }
+
+    //An iterator whose next value is the one at index, which must be below len. Decoding starts at
+    //block_start, so at most block_len values are decoded to reach it.
+    fn iter_from_index<'a, 'b>(
+        &'a self,
+        index: usize,
+        descriptor_table: &'b DescriptorTable,
+    ) -> ValueIter<'a, 'b> {
+        let mut iter = self.iter_values(descriptor_table);
+        let start = self.block_start(index);
+        iter.rewind(start);
+
+        let skip = index - start.ordinal as usize;
+        if skip > 0 {
+            iter.nth(skip - 1);
+        }
+        iter
+    }
+
+    //The start of the block holding the value at index, for block aligned sequences, and the start
+    //of the sequence otherwise
+    fn block_start(&self, index: usize) -> CursorMark {
+        let block_len = self.block_len();
+        if block_len == 0 || index < block_len {
+            return CursorMark {
+                byte_offset: 0,
+                last_value: 0,
+                ordinal: 0,
+            };
+        }
+
+        let block = index / block_len;
+        CursorMark {
+            byte_offset: self.blocks[block].byte_offset as usize,
+            last_value: self.blocks[block - 1].last_value,
+            ordinal: (block * block_len) as u32,
+        }
+    }
+
+    //Appends the values to output, like get_values, in one tight loop over the byte stream
+    pub fn decode_into(&self, output: &mut Vec<u32>, descriptor_table: &DescriptorTable) {
+        self.decode_groups_into(self.block_start(0), self.len(), descriptor_table, output);
+    }
+
+    //Appends the values at the indices in range to output. The range is cut off at the end of the
+    //sequence. Decoding starts at block_start, and the values before the range are dropped.
+    pub fn decode_range_into(
+        &self,
+        range: Range<usize>,
+        output: &mut Vec<u32>,
+        descriptor_table: &DescriptorTable,
+    ) {
+        let end = range.end.min(self.len());
+        if range.start >= end {
+            return;
+        }
+
+        let start = output.len();
+        let from = self.block_start(range.start);
+        let first = from.ordinal as usize;
+        self.decode_groups_into(from, end - first, descriptor_table, output);
+        output.drain(start..start + range.start - first);
+    }
+
+    //Decodes count values from the group at from, writing them straight into output, which is
+    //grown once. Two groups are decoded per iteration, which the AVX2 backend shuffles at once,
+    //as long as the 16 byte loads of both stay inside the stream.
+    fn decode_groups_into(
+        &self,
+        from: CursorMark,
+        count: usize,
+        descriptor_table: &DescriptorTable,
+        output: &mut Vec<u32>,
+    ) {
+        if self.run_escapes {
+            let mut iter = self.iter_values(descriptor_table);
+            iter.rewind(from);
+            output.extend(iter.take(count));
+            return;
+        }
+
+        let start = output.len();
+        //Room for whole groups, so the padding of the final group can be written and cut off
+        output.resize(start + count.next_multiple_of(4), 0);
+        let out = &mut output[start..];
+        let byte_stream = &self.byte_stream[..];
+        let mut descriptor_index = from.byte_offset;
+        let mut last = from.last_value;
+        let mut written = 0;
+
+        while written + 8 <= out.len() {
+            let first_entry =
+                descriptor_table.get_entry_for_descriptor(byte_stream[descriptor_index]);
+            let second_index = descriptor_index + first_entry.length as usize + 1;
+            if second_index + 17 > byte_stream.len() {
+                break;
+            }
+            let second_entry = descriptor_table.get_entry_for_descriptor(byte_stream[second_index]);
+
+            let chunks = descriptor_table.decode_chunk_pair(
+                [
+                    byte_stream[descriptor_index + 1..descriptor_index + 17]
+                        .try_into()
+                        .unwrap(),
+                    byte_stream[second_index + 1..second_index + 17]
+                        .try_into()
+                        .unwrap(),
+                ],
+                [
+                    &first_entry.shuffle_sequence,
+                    &second_entry.shuffle_sequence,
+                ],
+            );
+            for mut chunk in chunks {
+                self.chunk_to_values(&mut chunk, last);
+                last = chunk[3];
+                out[written..written + 4].copy_from_slice(&chunk);
+                written += 4;
+            }
+            descriptor_index = second_index + second_entry.length as usize + 1;
+        }
+
+        //The remaining groups one at a time, decoding safely near the end of the stream
+        while written < out.len() && descriptor_index < byte_stream.len() {
+            let descriptor = byte_stream[descriptor_index];
+            let desc_entry = descriptor_table.get_entry_for_descriptor(descriptor);
+            let mut chunk = if descriptor_index + 17 <= byte_stream.len() {
+                let bytes = byte_stream[descriptor_index + 1..descriptor_index + 17]
+                    .try_into()
+                    .unwrap();
+                descriptor_table.decode_chunk(bytes, &desc_entry.shuffle_sequence)
+            } else {
+                decode_chunk_safe_non_simd(descriptor, &byte_stream[descriptor_index + 1..])
+            };
+            self.chunk_to_values(&mut chunk, last);
+            last = chunk[3];
+            out[written..written + 4].copy_from_slice(&chunk);
+            written += 4;
+            descriptor_index += desc_entry.length as usize + 1;
+        }
+
+        output.truncate(start + count);
+    }
+
+    //Checks that the descriptors exactly cover the byte stream, that they describe len values,
+    //and that the block headers tile the stream. Escaped runs must continue the values before them
+    //by whole groups. The values must fit in u32, and top, the last value of every block, and for
+    //absolute sequences max_gap, must match them. Sequences that pass can be decoded without
+    //further bounds checks on the descriptors, and their header fields can be trusted.
+    pub fn validate(&self) -> Result<(), CodecError> {
+        //Escaped runs are only written for gaps, without block headers
+        if self.run_escapes && (self.gap_mode == GapMode::Absolute || !self.blocks.is_empty()) {
+            return Err(CodecError::InvalidEscape { offset: 0 });
+        }
+
+        let len = self.len();
+        let absolute = self.gap_mode == GapMode::Absolute;
+        let mut values = 0;
+        let mut descriptor_index = 0;
+        //The last value so far, and the largest, which is only needed for absolute values
+        let mut last: u32 = 0;
+        let mut max_value = 0;
+
+        while descriptor_index < self.byte_stream.len() {
+            let remaining_values = len - values;
+            if remaining_values == 0 {
+                return Err(CodecError::TrailingBytes {
+                    offset: descriptor_index,
+                });
+            }
+
+            if self.run_escapes
+                && self.byte_stream[descriptor_index] == 0
+                && self.byte_stream.get(descriptor_index + 1) == Some(&0)
+            {
+                let end = escape_end(&self.byte_stream, descriptor_index).ok_or(
+                    CodecError::TruncatedGroup {
+                        offset: descriptor_index,
+                    },
+                )?;
+                let run = end.wrapping_sub(last) as usize;
+                if end <= last || !run.is_multiple_of(4) || run > remaining_values {
+                    return Err(CodecError::InvalidEscape {
+                        offset: descriptor_index,
+                    });
+                }
+                values += run;
+                last = end;
+                descriptor_index += ESCAPE_LEN;
+                continue;
+            }
+
+            let descriptor = self.byte_stream[descriptor_index];
+            let values_in_group = remaining_values.min(4);
+            let mut used_end = descriptor_index + 1;
+            for i in 0..values_in_group {
+                used_end += descriptor_length_i(descriptor, i) as usize;
+            }
+            let full_end = descriptor_index + 1 + descriptor_length_total(descriptor) as usize;
+
+            if used_end > self.byte_stream.len()
+                || (values_in_group == 4 && full_end > self.byte_stream.len())
+            {
+                return Err(CodecError::TruncatedGroup {
+                    offset: descriptor_index,
+                });
+            }
+
+            //The last group is either cut off after its last value, or padded to all four
+            if values_in_group < 4
+                && self.byte_stream.len() != used_end
+                && self.byte_stream.len() != full_end
+            {
+                let offset = if self.byte_stream.len() > full_end {
+                    full_end
+                } else {
+                    used_end
+                };
+                return Err(CodecError::TrailingBytes { offset });
+            }
+
+            //The values are followed, as top, max_gap and the block headers are trusted later
+            let gaps = decode_chunk_safe_non_simd(
+                descriptor,
+                &self.byte_stream[descriptor_index + 1..used_end],
+            );
+            for gap in gaps[..values_in_group].iter() {
+                last = if absolute {
+                    *gap
+                } else {
+                    last.checked_add(*gap).ok_or(CodecError::ValueOverflow {
+                        offset: descriptor_index,
+                    })?
+                };
+                max_value = max_value.max(last);
+            }
+
+            values += values_in_group;
+            descriptor_index = full_end.min(self.byte_stream.len());
+
+            //Blocks end at group boundaries, so the walk passes the end of every block
+            let block_len = self.block_len();
+            if block_len > 0 && (values.is_multiple_of(block_len) || values == len) {
+                let block = (values - 1) / block_len;
+                if self
+                    .blocks
+                    .get(block)
+                    .is_some_and(|header| header.last_value != last)
+                {
+                    return Err(CodecError::InvalidBlockHeader { block });
+                }
+            }
+        }
+
+        if values != len {
+            return Err(CodecError::LengthMismatch {
+                expected: len,
+                found: values,
+            });
+        }
+        if self.top != last {
+            return Err(CodecError::HeaderMismatch { field: "top" });
+        }
+        //Only absolute sequences read the largest value from the stats
+        if absolute && self.stats.max_gap != max_value {
+            return Err(CodecError::HeaderMismatch { field: "max_gap" });
+        }
+
+        self.validate_blocks()
+    }
+
+    fn validate_blocks(&self) -> Result<(), CodecError> {
+        //Block headers are only written for gaps
+        if self.gap_mode == GapMode::Absolute && !self.blocks.is_empty() {
+            return Err(CodecError::InvalidBlockHeader { block: 0 });
+        }
+        //Blocks end at group boundaries, which the factory enforces
+        if self.block_len & 3 != 0 {
+            return Err(CodecError::InvalidBlockHeader { block: 0 });
+        }
+        if self.blocks.is_empty() && (self.block_len == 0 || self.len() == 0) {
+            return Ok(());
+        }
+
+        let block_len = self.block_len();
+        let expected_blocks = if block_len == 0 {
+            0
+        } else {
+            self.len().div_ceil(block_len)
+        };
+        if self.blocks.len() != expected_blocks {
+            return Err(CodecError::InvalidBlockHeader {
+                block: self.blocks.len().min(expected_blocks),
+            });
+        }
+
+        let mut byte_offset = 0;
+        let mut last_value = 0;
+        for (block, header) in self.blocks.iter().enumerate() {
+            if header.byte_offset as usize != byte_offset || header.last_value < last_value {
+                return Err(CodecError::InvalidBlockHeader { block });
+            }
+            byte_offset += header.byte_len as usize;
+            last_value = header.last_value;
+        }
+        if byte_offset != self.byte_stream.len() {
+            return Err(CodecError::InvalidBlockHeader {
+                block: self.blocks.len().saturating_sub(1),
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn stats(&self) -> GapStats {
+        self.stats
+    }
+
+    pub fn fits_u16(&self) -> bool {
+        //The largest absolute value is the largest gap
+        let max_value = match self.gap_mode {
+            GapMode::Absolute => self.stats.max_gap,
+            _ => self.top,
+        };
+        max_value <= u16::MAX as u32
+    }
+
+    //The first value, read straight from the first group without a descriptor table
+    pub fn first(&self) -> Option<u32> {
+        if self.len == 0 {
+            return None;
+        }
+        //An escaped run at the start continues from 0
+        if self.run_escapes && escape_end(&self.byte_stream, 0).is_some() {
+            return Some(1);
+        }
+        let int_len = (self.byte_stream[0] & 3) as usize + 1;
+        let mut bytes = [0; 4];
+        bytes[..int_len].copy_from_slice(&self.byte_stream[1..1 + int_len]);
+        Some(u32::from_ne_bytes(bytes))
+    }
+
+    //The last value, or 0 if the sequence is empty
+    pub fn top(&self) -> u32 {
+        self.top
+    }
+
+    pub fn gap_mode(&self) -> GapMode {
+        self.gap_mode
+    }
+
+    //Whether long runs of consecutive values may be written as escape groups
+    pub fn has_run_escapes(&self) -> bool {
+        self.run_escapes
+    }
+
+    //The number of escaped runs, and the number of values in them, found from the descriptors
+    pub fn escaped_runs(&self) -> (usize, usize) {
+        let mut runs = 0;
+        let mut groups: usize = 0;
+        let mut index = 0;
+        while index < self.byte_stream.len() {
+            if self.run_escapes && escape_end(&self.byte_stream, index).is_some() {
+                runs += 1;
+                index += ESCAPE_LEN;
+            } else {
+                groups += 1;
+                index += 1 + descriptor_length_total(self.byte_stream[index]) as usize;
+            }
+        }
+
+        //Escaped runs are whole groups, so only the final regular group can be partial
+        let regular_values = (groups * 4).saturating_sub((4 - self.len() % 4) % 4);
+        (runs, self.len() - regular_values)
+    }
+
+    //Turns a chunk of stored numbers into values. Gaps are added up from last, and absolute
+    //values are kept as they are.
+    #[inline(always)]
+    fn chunk_to_values(&self, chunk: &mut [u32; 4], last: u32) {
+        if self.gap_mode != GapMode::Absolute {
+            delta_chunk_to_value_chunk(chunk, last);
+        }
+    }
+}
+
+/*
+Serialized layout, with every u32 little endian:
+
+[Version (u8)] [Len] [Gap mode (u8)] [Flags (u8)] [Top] [Block len] [Gap stats (7 x u32)]
+[Number of blocks] [Last value, byte offset, byte len] for each block
+[Byte stream length] [Byte stream]
+
+The byte stream is last, so from_slice can borrow it from the input without copying.
+The only flag is FLAG_RUN_ESCAPES. Version 2 had no flags, and version 1 had no gap mode either,
+and is read as NonStrict.
+*/
+pub const FORMAT_VERSION: u8 = 3;
+
+//Set if the byte stream may hold escaped runs
+const FLAG_RUN_ESCAPES: u8 = 1;
+
+//Bytes before the block headers in the current version: version, length, gap mode, flags, and
+//ten u32 fields from top to the block count
+const HEADER_LEN: usize = 1 + 4 + 1 + 1 + 4 * 10;
+
+impl<'a> VarintGB<'a> {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            HEADER_LEN + 4 * (3 * self.blocks.len() + 1) + self.byte_stream.len(),
+        );
+        bytes.push(FORMAT_VERSION);
+        bytes.extend_from_slice(&self.len.to_le_bytes());
+        bytes.push(self.gap_mode.to_u8());
+        bytes.push(if self.run_escapes {
+            FLAG_RUN_ESCAPES
+        } else {
+            0
+        });
+
+        let stats = self.stats;
+        let header = [
+            self.top,
+            self.block_len,
+            stats.max_gap,
+            stats.gap_one_runs,
+            stats.longest_gap_one_run,
+            stats.bytes_per_length[0],
+            stats.bytes_per_length[1],
+            stats.bytes_per_length[2],
+            stats.bytes_per_length[3],
+            self.blocks.len() as u32,
+        ];
+        for field in header {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        debug_assert_eq!(bytes.len(), HEADER_LEN);
+        for block in self.blocks.iter() {
+            bytes.extend_from_slice(&block.last_value.to_le_bytes());
+            bytes.extend_from_slice(&block.byte_offset.to_le_bytes());
+            bytes.extend_from_slice(&block.byte_len.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.byte_stream.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.byte_stream);
+        bytes
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    //Loads a sequence written by to_bytes, borrowing the byte stream from the input.
+    //The sequence is validated, so it is safe to decode.
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self, CodecError> {
+        let mut reader = FrameReader::new(bytes);
+
+        let version = reader.read_u8()?;
+        if !(1..=FORMAT_VERSION).contains(&version) {
+            return Err(CodecError::UnsupportedVersion { version });
+        }
+
+        let len = reader.read_u32()?;
+        let gap_mode = if version == 1 {
+            GapMode::NonStrict
+        } else {
+            match reader.read_u8()? {
+                mode @ (1 | 2) => GapMode::from_u8(mode).unwrap(),
+                mode => return Err(CodecError::InvalidGapMode { mode }),
+            }
+        };
+        let flags = if version < 3 { 0 } else { reader.read_u8()? };
+        if flags & !FLAG_RUN_ESCAPES != 0 {
+            return Err(CodecError::InvalidFlags { flags });
+        }
+        let top = reader.read_u32()?;
+        let block_len = reader.read_u32()?;
+        let stats = GapStats {
+            max_gap: reader.read_u32()?,
+            gap_one_runs: reader.read_u32()?,
+            longest_gap_one_run: reader.read_u32()?,
+            bytes_per_length: [
+                reader.read_u32()?,
+                reader.read_u32()?,
+                reader.read_u32()?,
+                reader.read_u32()?,
+            ],
+        };
+
+        //Every header takes 12 bytes, so a corrupt count fails before allocating for it
+        let block_count = reader.read_u32()? as usize;
+        debug_assert!(version < FORMAT_VERSION || reader.offset() == HEADER_LEN);
+        let mut blocks = Vec::with_capacity(block_count.min(reader.remaining() / 12));
+        for _ in 0..block_count {
+            blocks.push(BlockHeader {
+                last_value: reader.read_u32()?,
+                byte_offset: reader.read_u32()?,
+                byte_len: reader.read_u32()?,
+            });
+        }
+
+        let stream_len = reader.read_u32()? as usize;
+        let byte_stream = reader.read_bytes(stream_len)?;
+        reader.finish()?;
+
+        let seq = VarintGB {
+            byte_stream: Cow::Borrowed(byte_stream),
+            len,
+            top,
+            stats,
+            blocks: blocks.into_boxed_slice(),
+            block_len,
+            gap_mode,
+            run_escapes: flags & FLAG_RUN_ESCAPES != 0,
+        };
+        seq.validate()?;
+        Ok(seq)
+    }
+
+    //Borrows the sequence a handle points at from the buffer it was written into. The sequence is
+    //validated, so it is safe to decode, but it has no gap stats.
+    pub fn from_handle(buffer: &'a [u8], handle: ListHandle) -> Result<Self, CodecError> {
+        let mut reader = FrameReader::new(buffer);
+        reader.read_bytes(handle.offset as usize)?;
+        let byte_stream = reader.read_bytes(handle.byte_len as usize)?;
+
+        let seq = VarintGB {
+            byte_stream: Cow::Borrowed(byte_stream),
+            len: handle.len,
+            top: handle.top,
+            ..VarintGB::new()
+        };
+        seq.validate()?;
+        Ok(seq)
+    }
+
+    //Copies the byte stream, if it is borrowed, so the sequence no longer refers to the input
+    pub fn into_owned(self) -> VarintGB<'static> {
+        VarintGB {
+            byte_stream: Cow::Owned(self.byte_stream.into_owned()),
+            ..self
+        }
+    }
+}
+
+impl VarintGB<'static> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        VarintGB::from_slice(bytes).map(VarintGB::into_owned)
+    }
+
+    //Reads a sequence written by write_to, until the end of the reader
+    pub fn from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+//Reusable state for decoding many sequences. The output buffer keeps its capacity between
+//sequences, so once it has grown to the longest sequence, decoding does not allocate.
+pub struct DecodeContext<'t> {
+    descriptor_table: &'t DescriptorTable,
+    values: Vec<u32>,
+}
+
+impl<'t> DecodeContext<'t> {
+    pub fn new(descriptor_table: &'t DescriptorTable) -> Self {
+        DecodeContext {
+            descriptor_table,
+            values: Vec::new(),
+        }
+    }
+
+    //Decodes the sequence into the context's buffer, replacing the previous values
+    pub fn decode(&mut self, seq: &VarintGB<'_>) -> &[u32] {
+        self.values.clear();
+        seq.decode_run_aware_into(self.descriptor_table, &mut self.values);
+        &self.values
+    }
 }
 
 pub struct VarintGBFactory {
@@ -95,6 +1077,20 @@ pub struct VarintGBFactory {
     bytes_in_current_chunk: u8,
     no_of_chunks: u32,
     len: u32,
+    stats: GapStats,
+    current_gap_one_run: u32,
+    block_aligned: bool,
+    block_len: usize,
+    blocks: Vec<BlockHeader>,
+    block_start: usize,
+    gap_mode: GapMode,
+    //Where the sequence starts in byte_stream, which is after other lists for new_in
+    list_start: usize,
+    run_escapes: bool,
+    //Consecutive values held back from the stream, as they may become an escaped run, and the
+    //value before them
+    pending_run: u32,
+    run_base: u32,
 }
 impl VarintGBFactory {
     pub fn new() -> Self {
@@ -106,31 +1102,159 @@ impl VarintGBFactory {
             bytes_in_current_chunk: 0,
             no_of_chunks: 0,
             len: 0,
+            stats: GapStats::default(),
+            current_gap_one_run: 0,
+            block_aligned: false,
+            block_len: 0,
+            blocks: Vec::new(),
+            block_start: 0,
+            gap_mode: GapMode::NonStrict,
+            list_start: 0,
+            run_escapes: false,
+            pending_run: 0,
+            run_base: 0,
         }
     }
 
-    pub fn get_top(&self) -> u32 {
-        self.top
+    //A factory that appends the sequence to the end of buffer, which may already hold other lists.
+    //into_handle returns the buffer with a handle to the sequence, so many lists can be written
+    //into one buffer without an allocation per list.
+    pub fn new_in(buffer: Vec<u8>) -> Self {
+        VarintGBFactory {
+            list_start: buffer.len(),
+            block_start: buffer.len(),
+            byte_stream: buffer,
+            ..Self::new()
+        }
     }
 
-    pub fn push_if_not_on_top(&mut self, num: u32) {
-        if self.top != num {
-            self.push_int(num);
+    //A factory that stores the values themselves instead of gaps, so they can repeat and come in
+    //any order, e.g. term frequencies. The sequence has no block headers.
+    pub fn new_absolute() -> Self {
+        VarintGBFactory {
+            gap_mode: GapMode::Absolute,
+            ..Self::new()
         }
     }
-    pub fn push_int(&mut self, x: u32) {
-        self.len += 1;
 
-        //If starting a new chunk, add descriptor, reset counter, and increment no of chunks
-        if self.index_in_chunk == 0 {
-            self.byte_stream.push(0);
-            self.descriptor_index = self.byte_stream.len() - 1;
-            self.bytes_in_current_chunk = 0;
-            self.no_of_chunks += 1;
+    //A factory that writes runs of at least MIN_ESCAPED_RUN consecutive values, that start at a
+    //group boundary, as escape groups. The sequence has no block headers.
+    pub fn new_with_run_escapes() -> Self {
+        VarintGBFactory {
+            run_escapes: true,
+            ..Self::new()
+        }
+    }
+
+    //A factory that closes a block every BLOCK_LEN values, and records a header for each block.
+    //The final group is padded, so every block ends at a group boundary.
+    pub fn new_block_aligned() -> Self {
+        Self::new_with_block_len(BLOCK_LEN)
+    }
+
+    //Like new_block_aligned, with a block length that must be a positive multiple of 4
+    pub fn new_with_block_len(block_len: usize) -> Self {
+        assert!(block_len > 0 && block_len & 3 == 0);
+        VarintGBFactory {
+            block_aligned: true,
+            block_len,
+            ..Self::new()
+        }
+    }
+
+    //Like new_block_aligned, with the block length picked by auto_block_len for a list of
+    //about expected_len values
+    pub fn new_auto_block_len(expected_len: usize) -> Self {
+        Self::new_with_block_len(auto_block_len(expected_len))
+    }
+
+    pub fn get_top(&self) -> u32 {
+        self.top
+    }
+
+    //Reserves room for at least additional more values, at one byte each plus their descriptors
+    pub fn reserve(&mut self, additional: usize) {
+        self.byte_stream
+            .reserve(additional + additional.div_ceil(4));
+    }
+
+    pub fn push_if_not_on_top(&mut self, num: u32) {
+        if self.top != num {
+            self.push_int(num);
         }
+    }
+    pub fn push_int(&mut self, x: u32) {
+        let absolute = self.gap_mode == GapMode::Absolute;
+        assert!(
+            absolute || x >= self.top,
+            "values must be pushed in increasing order"
+        );
+        self.len += 1;
 
-        let delta = x - self.top;
+        let delta = if absolute { x } else { x - self.top };
+        let previous = self.top;
         self.top = x;
+        self.update_stats(delta);
+
+        if self.run_escapes {
+            //A run is held back from the start of a group until it ends
+            if delta == 1 && (self.pending_run > 0 || self.index_in_chunk == 0) {
+                if self.pending_run == 0 {
+                    self.run_base = previous;
+                }
+                self.pending_run += 1;
+                return;
+            }
+            self.flush_run();
+        }
+        self.write_delta(delta);
+        self.close_full_block();
+    }
+
+    //Pushes the value a gap after the top, with the bytes of the gap as another sequence stored
+    //them, so they are copied instead of encoded again
+    fn push_gap_bytes(&mut self, gap_bytes: &[u8]) {
+        let delta = gap_from_bytes(gap_bytes);
+        self.len += 1;
+        self.top += delta;
+        self.update_stats(delta);
+        self.write_gap_bytes(gap_bytes);
+        self.close_full_block();
+    }
+
+    fn close_full_block(&mut self) {
+        if self.block_aligned && self.len as usize == (self.blocks.len() + 1) * self.block_len {
+            self.close_block();
+        }
+    }
+
+    //Writes the values of the run held back, as an escape group if it is long enough. The values
+    //that do not fill a group are written after it as gaps of 1.
+    fn flush_run(&mut self) {
+        let run = std::mem::take(&mut self.pending_run);
+        let escaped = if run >= MIN_ESCAPED_RUN {
+            run - run % 4
+        } else {
+            0
+        };
+        if escaped > 0 {
+            self.byte_stream.extend_from_slice(&[0, 0]);
+            self.byte_stream
+                .extend_from_slice(&(self.run_base + escaped).to_le_bytes());
+            self.no_of_chunks += 1;
+        }
+        for _ in escaped..run {
+            self.write_delta(1);
+        }
+    }
+
+    fn write_delta(&mut self, delta: u32) {
+        //A first gap of 0 in one byte would start the group like an escape group
+        let min_len = if self.run_escapes && self.index_in_chunk == 0 && delta == 0 {
+            2
+        } else {
+            1
+        };
 
         //Transmute to a slice of bytes
         let x_bytes_sized: [u8; 4] = delta.to_ne_bytes();
@@ -141,378 +1265,1933 @@ impl VarintGBFactory {
         //Remove trailing 0 bytes.
         //fx 2: 0000-0000 0000-0000 0000-0000 0000-0010
         //becomes 0000-0010
-        while x_bytes.len() > 1 && x_bytes[x_bytes.len() - 1] == 0 {
+        while x_bytes.len() > min_len && x_bytes[x_bytes.len() - 1] == 0 {
             x_bytes = &x_bytes[..x_bytes.len() - 1];
         }
 
-        //Push the bytes to the bitstream
-        for byte in x_bytes {
-            self.byte_stream.push(*byte);
+        self.write_gap_bytes(x_bytes);
+    }
+
+    //Appends the 1 to 4 bytes of a gap, and their length to the descriptor of the current group
+    fn write_gap_bytes(&mut self, gap_bytes: &[u8]) {
+        //If starting a new chunk, add descriptor, reset counter, and increment no of chunks
+        if self.index_in_chunk == 0 {
+            self.byte_stream.push(0);
+            self.descriptor_index = self.byte_stream.len() - 1;
+            self.bytes_in_current_chunk = 0;
+            self.no_of_chunks += 1;
         }
 
+        //Push the bytes to the bitstream
+        self.byte_stream.extend_from_slice(gap_bytes);
+
+        self.stats.bytes_per_length[gap_bytes.len() - 1] += gap_bytes.len() as u32;
+
         //We push the length of the int to the descriptor
-        let mut int_len = (x_bytes.len() - 1) as u8;
+        let mut int_len = (gap_bytes.len() - 1) as u8;
         self.bytes_in_current_chunk += int_len;
 
         //We rotate it, such that it fits in the right place in the descriptor:
         int_len <<= self.index_in_chunk * 2;
 
-        self.byte_stream[self.descriptor_index] ^= int_len;
+        self.byte_stream[self.descriptor_index] ^= int_len;
+
+        self.index_in_chunk = (self.index_in_chunk + 1) % 4;
+    }
+
+    fn close_block(&mut self) {
+        self.blocks.push(BlockHeader {
+            last_value: self.top,
+            byte_offset: self.block_start as u32,
+            byte_len: (self.byte_stream.len() - self.block_start) as u32,
+        });
+        self.block_start = self.byte_stream.len();
+    }
+
+    //Fills the rest of the current group with 1 byte deltas of 0, which the descriptor already
+    //describes, as its bits start out as 0
+    fn pad_group(&mut self) {
+        while self.index_in_chunk != 0 {
+            self.byte_stream.push(0);
+            self.index_in_chunk = (self.index_in_chunk + 1) % 4;
+        }
+    }
+
+    fn update_stats(&mut self, delta: u32) {
+        self.stats.max_gap = self.stats.max_gap.max(delta);
+
+        if delta == 1 {
+            if self.current_gap_one_run == 0 {
+                self.stats.gap_one_runs += 1;
+            }
+            self.current_gap_one_run += 1;
+            self.stats.longest_gap_one_run =
+                self.stats.longest_gap_one_run.max(self.current_gap_one_run);
+        } else {
+            self.current_gap_one_run = 0;
+        }
+    }
+
+    pub fn into_varint_gb(mut self) -> VarintGB<'static> {
+        self.flush_run();
+        if self.block_aligned && self.len as usize > self.blocks.len() * self.block_len {
+            self.pad_group();
+            self.close_block();
+        }
+        if self.list_start > 0 {
+            self.byte_stream.drain(..self.list_start);
+        }
+
+        VarintGB {
+            byte_stream: Cow::Owned(self.byte_stream),
+            len: self.len,
+            top: self.top,
+            stats: self.stats,
+            blocks: self.blocks.into_boxed_slice(),
+            block_len: self.block_len as u32,
+            gap_mode: self.gap_mode,
+            run_escapes: self.run_escapes,
+        }
+    }
+
+    //Returns the buffer, and a handle to where the sequence was written in it. Handles do not
+    //carry block headers, gap stats, a gap mode or flags, so the factory must not be block
+    //aligned, absolute or write escaped runs.
+    pub fn into_handle(self) -> (Vec<u8>, ListHandle) {
+        assert!(
+            !self.block_aligned && self.gap_mode == GapMode::NonStrict && !self.run_escapes,
+            "only plain sequences can be written as handles"
+        );
+
+        let handle = ListHandle {
+            offset: self.list_start as u32,
+            len: self.len,
+            byte_len: (self.byte_stream.len() - self.list_start) as u32,
+            top: self.top,
+        };
+        (self.byte_stream, handle)
+    }
+}
+
+//Where a sequence written by a factory from new_in lies in the shared buffer, and what is needed
+//to decode it from there
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ListHandle {
+    pub offset: u32,
+    pub len: u32,
+    pub byte_len: u32,
+    pub top: u32,
+}
+
+//The lengths of the four values of a group, from its descriptor
+fn descriptor_lengths(descriptor: u8) -> [u8; 4] {
+    [0, 1, 2, 3].map(|i| descriptor_length_i(descriptor, i))
+}
+
+#[cfg(test)]
+fn deltas_to_values(deltas: &[u32]) -> Vec<u32> {
+    let mut values = Vec::with_capacity(deltas.len());
+    let mut last = 0;
+    for delta in deltas.iter() {
+        if *delta == 0 {
+            continue;
+        }
+        last += delta;
+        values.push(last);
+    }
+    values
+}
+
+fn descriptor_length_i(descriptor: u8, index: usize) -> u8 {
+    let mut mask = 0b00000011u8;
+    mask <<= index * 2;
+    let len = descriptor & mask;
+    (len >> (index * 2)) + 1
+}
+
+//The gap stored in the 1 to 4 bytes of one value
+fn gap_from_bytes(gap_bytes: &[u8]) -> u32 {
+    let mut bytes = [0; 4];
+    bytes[..gap_bytes.len()].copy_from_slice(gap_bytes);
+    u32::from_ne_bytes(bytes)
+}
+
+fn descriptor_length_total(descriptor: u8) -> u8 {
+    let mut length = 0;
+    for i in 0..4 {
+        length += descriptor_length_i(descriptor, i);
+    }
+    length
+}
+
+//The high nibbles of the shuffle indices in the word that are neither below 16 nor 0xFF, checked 8
+//at a time. A byte of !word is 0 exactly where the index is 0xFF, which the zero byte test marks
+//with its top bit. The mark is widened to the high nibble, which clears it for those indices.
+fn invalid_shuffle_indices(word: u64) -> u64 {
+    const LOW_7: u64 = 0x7F7F_7F7F_7F7F_7F7F;
+    let inverted = !word;
+    let is_ff = !(((inverted & LOW_7) + LOW_7) | inverted | LOW_7);
+    word & 0xF0F0_F0F0_F0F0_F0F0 & !((is_ff >> 7) * 0xF0)
+}
+
+#[derive(Copy, Clone)]
+pub struct DescriptorEntry {
+    shuffle_sequence: Shuffle,
+    length: u8,
+}
+
+//The entry is copied out of the table on every chunk, so it must stay one 32 byte, 16 aligned slot
+const _: () = assert!(std::mem::size_of::<DescriptorEntry>() == 32);
+const _: () = assert!(std::mem::align_of::<DescriptorEntry>() == 16);
+
+pub struct DescriptorTable {
+    table: Vec<DescriptorEntry>,
+    table_u16: Vec<Shuffle>,
+    //Only ever set to a backend the CPU supports, which makes the shuffles safe to run
+    backend: Backend,
+}
+
+//A handle to a table that can be shared by any number of iterators, across threads
+pub type SharedDescriptorTable = Arc<DescriptorTable>;
+
+//The table is read-only after construction, so it must stay shareable across threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<DescriptorTable>();
+};
+
+static SHARED_DESCRIPTOR_TABLE: OnceLock<SharedDescriptorTable> = OnceLock::new();
+
+impl DescriptorTable {
+    //A table that decodes with the fastest backend the CPU supports
+    pub fn new() -> Self {
+        Self::with_backend(Backend::detect())
+    }
+
+    //A table that decodes with the given backend, e.g. to compare against the scalar decoder.
+    //Panics if the CPU does not support it.
+    pub fn with_backend(backend: Backend) -> Self {
+        assert!(backend.is_supported(), "{backend:?} is not supported");
+
+        let mut table = Vec::with_capacity(256);
+        let mut table_u16 = Vec::with_capacity(256);
+        for descriptor in 0..=255 {
+            table.push(Self::create_entry_for_descriptor(descriptor));
+            //Only the two lowest bytes of each value are kept, so the values must fit in a u16
+            table_u16.push(shuffle_for_lengths(&descriptor_lengths(descriptor), 2));
+        }
+
+        DescriptorTable {
+            table,
+            table_u16,
+            backend,
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    //Returns a handle to a process-wide table, which is only built on first use
+    pub fn shared() -> SharedDescriptorTable {
+        Self::shared_static().clone()
+    }
+
+    //Borrows the process-wide table, for iterators that must not be tied to a table of their own
+    pub fn global() -> &'static DescriptorTable {
+        Self::shared_static()
+    }
+
+    fn shared_static() -> &'static SharedDescriptorTable {
+        SHARED_DESCRIPTOR_TABLE.get_or_init(|| Arc::new(DescriptorTable::new()))
+    }
+
+    //Size of a serialized table: per descriptor, the shuffle sequence, the u16 shuffle sequence
+    //and the total length of the chunk
+    pub const SERIALIZED_LEN: usize = 256 * (16 + 16 + 1);
+
+    //Serializes the table, such that it can be stored next to the data, or embedded in the
+    //binary with include_bytes!, instead of being recomputed on every start
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SERIALIZED_LEN);
+        for (entry, shuffle_u16) in self.table.iter().zip(self.table_u16.iter()) {
+            bytes.extend_from_slice(&entry.shuffle_sequence.0);
+            bytes.extend_from_slice(&shuffle_u16.0);
+            bytes.push(entry.length);
+        }
+        debug_assert_eq!(bytes.len(), Self::SERIALIZED_LEN);
+        bytes
+    }
+
+    //Loads a table written by to_bytes. The backend is detected again, as the table may have
+    //been written on another CPU.
+    //The entries are checked against the bytes alone, so loading stays cheaper than building. The
+    //decoders trust the lengths to move through the byte stream, so each must be the sum of the
+    //descriptor's lengths. Shuffle indices past 15 give different results per backend, so every
+    //index must be below 16, or 0xFF for a zero byte.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        let mut reader = FrameReader::new(bytes);
+        let entries = reader.read_bytes(Self::SERIALIZED_LEN)?;
+        reader.finish()?;
+
+        let mut table = Vec::with_capacity(256);
+        let mut table_u16 = Vec::with_capacity(256);
+        for (descriptor, entry) in (0..=255).zip(entries.chunks_exact(33)) {
+            let length = entry[32];
+            let invalid_indices = entry[..32]
+                .chunks_exact(8)
+                .map(|word| invalid_shuffle_indices(u64::from_le_bytes(word.try_into().unwrap())))
+                .fold(0, |invalid, word| invalid | word);
+            if length != descriptor_length_total(descriptor) || invalid_indices != 0 {
+                return Err(CodecError::InvalidDescriptorEntry { descriptor });
+            }
+            table.push(DescriptorEntry {
+                shuffle_sequence: Shuffle(entry[..16].try_into().unwrap()),
+                length,
+            });
+            table_u16.push(Shuffle(entry[16..32].try_into().unwrap()));
+        }
+
+        Ok(DescriptorTable {
+            table,
+            table_u16,
+            backend: Backend::detect(),
+        })
+    }
+
+    fn create_entry_for_descriptor(descriptor: u8) -> DescriptorEntry {
+        let shf = shuffle_for_lengths(&descriptor_lengths(descriptor), 4);
+        let length = descriptor_length_total(descriptor);
+        DescriptorEntry {
+            shuffle_sequence: shf,
+            length,
+        }
+    }
+
+    #[inline(always)]
+    pub fn get_entry_for_descriptor(&self, descriptor: u8) -> DescriptorEntry {
+        self.table[descriptor as usize]
+    }
+
+    #[inline(always)]
+    pub fn get_shuffle_u16_for_descriptor(&self, descriptor: u8) -> Shuffle {
+        self.table_u16[descriptor as usize]
+    }
+
+    pub fn get_shuffle_for_descriptor(&self, descriptor: u8) -> Shuffle {
+        self.get_entry_for_descriptor(descriptor).shuffle_sequence
+    }
+
+    pub fn get_length_for_descriptor(&self, descriptor: u8) -> u8 {
+        self.get_entry_for_descriptor(descriptor).length
+    }
+
+    //Shuffles the bytes of a chunk into four values, with the backend of the table
+    #[inline(always)]
+    pub fn shuffle_chunk(&self, chunk: &[u8; 16], shuffle_sequence: &Shuffle) -> [u8; 16] {
+        unsafe { shuffle_bytes(self.backend, chunk, shuffle_sequence) }
+    }
+
+    #[inline(always)]
+    pub fn decode_chunk(&self, chunk: &[u8; 16], shuffle_sequence: &Shuffle) -> [u32; 4] {
+        let shuffled = self.shuffle_chunk(chunk, shuffle_sequence);
+        unsafe { std::mem::transmute::<[u8; 16], [u32; 4]>(shuffled) }
+    }
+
+    //Like decode_chunk, reading the 16 bytes of the chunk from chunk_addr
+    #[inline(always)]
+    pub fn decode_chunk_by_address(
+        &self,
+        chunk_addr: *const u8,
+        shuffle_sequence: &Shuffle,
+    ) -> [u32; 4] {
+        let chunk = unsafe { ptr::read_unaligned(chunk_addr as *const [u8; 16]) };
+        self.decode_chunk(&chunk, shuffle_sequence)
+    }
+
+    //Decodes two chunks into four values each. The AVX2 backend shuffles both at once.
+    #[inline(always)]
+    pub fn decode_chunk_pair(
+        &self,
+        chunks: [&[u8; 16]; 2],
+        shuffle_sequences: [&Shuffle; 2],
+    ) -> [[u32; 4]; 2] {
+        let shuffled = unsafe { shuffle_bytes_pair(self.backend, chunks, shuffle_sequences) };
+        unsafe { std::mem::transmute::<[u8; 32], [[u32; 4]; 2]>(shuffled) }
+    }
+}
+
+#[inline(always)]
+pub fn decode_chunk_safe_non_simd(descriptor: u8, byte_stream: &[u8]) -> [u32; 4] {
+    let mut chunk = [0; 4];
+    let mut index = 0;
+    for i in 0..4 {
+        let len = descriptor_length_i(descriptor, i);
+        let mut num = 0;
+        for pos in 0..len {
+            if index >= byte_stream.len() {
+                return chunk;
+            }
+            num += (byte_stream[index] as u32) << (pos * 8);
+            index += 1;
+        }
+        chunk[i] = num;
+    }
+    chunk
+}
+
+#[test]
+pub fn test_safe_decoder_non_simd() {
+    let byte_stream = [0b11111001, 0b11111111, 1, 1, 1];
+    let descriptor = 0b00000001u8;
+    let vals = decode_chunk_safe_non_simd(descriptor, &byte_stream);
+    assert_eq!(vals, [0b1111111111111001, 1, 1, 1]);
+}
+pub struct IterUnsafe<'b> {
+    descriptor_table: &'b DescriptorTable,
+    src: *mut u8,
+    chunks_to_decode: u32,
+}
+
+impl Iterator for IterUnsafe<'_> {
+    type Item = [u32; 4];
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.chunks_to_decode < 1 {
+            return None;
+        }
+
+        unsafe {
+            let descriptor: u8 = *self.src;
+            let chunk_addr = self.src.add(1);
+
+            let descriptor_entry = self.descriptor_table.get_entry_for_descriptor(descriptor);
+            let shf = descriptor_entry.shuffle_sequence;
+
+            //println!("{}", chunk_addr as u32);
+
+            let chunk = self
+                .descriptor_table
+                .decode_chunk_by_address(chunk_addr, &shf);
+            self.src = self.src.add(descriptor_entry.length as usize + 1);
+            self.chunks_to_decode -= 1;
+            Some(chunk)
+        }
+    }
+}
+pub struct Iter<'a, 'b> {
+    descriptor_table: &'b DescriptorTable,
+    byte_stream: &'a [u8],
+    len: u32,
+    descriptor_index: usize,
+    last_top: u32,
+    blocks: &'a [BlockHeader],
+    block_len: u32,
+    //Index of the first value of the next chunk
+    value_index: u32,
+    //The chunk next_geq last stopped in, the position after the value it returned, and the number
+    //of values in it that are not padding
+    chunk: [u32; 4],
+    chunk_pos: usize,
+    chunk_len: usize,
+    //Absolute values are not added to the previous value
+    absolute: bool,
+    run_escapes: bool,
+}
+impl Iter<'_, '_> {
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    //Returns the first value >= target at or after the current position. If the sequence has block
+    //headers, blocks that end below the target are skipped without decoding them.
+    //The rest of the chunk holding the value is kept for the next call of next_geq, while next
+    //continues from the chunk after it.
+    pub fn next_geq(&mut self, target: u32) -> Option<u32> {
+        assert!(!self.absolute, "absolute values are not sorted");
+        if let Some(val) = self.take_from_chunk(target) {
+            return Some(val);
+        }
+
+        if !self.skip_blocks(target) {
+            return None;
+        }
+
+        loop {
+            self.skip_run(target);
+            let chunk = self.next()?;
+            self.chunk = chunk;
+            self.chunk_pos = 0;
+            self.chunk_len = (self.len - (self.value_index - 4)).min(4) as usize;
+            if let Some(val) = self.take_from_chunk(target) {
+                return Some(val);
+            }
+        }
+    }
+
+    //Skips the groups of an escaped run at the current position that end below target, without
+    //expanding them
+    fn skip_run(&mut self, target: u32) {
+        if !self.run_escapes || self.descriptor_index >= self.byte_stream.len() {
+            return;
+        }
+        let Some(end) = escape_end(self.byte_stream, self.descriptor_index) else {
+            return;
+        };
+
+        let skipped = if target > end {
+            end - self.last_top
+        } else {
+            target.saturating_sub(self.last_top + 1) / 4 * 4
+        };
+        self.last_top += skipped;
+        self.value_index += skipped;
+        if self.last_top == end {
+            self.descriptor_index += ESCAPE_LEN;
+        }
+    }
+
+    //The next group of the escaped run ending at end. The iterator stays on the escape group until
+    //the run is used up, so the value before a group is all a mark inside the run has to hold.
+    fn next_in_run(&mut self, end: u32) -> [u32; 4] {
+        let base = self.last_top;
+        self.last_top = base + 4;
+        if self.last_top == end {
+            self.descriptor_index += ESCAPE_LEN;
+        }
+        [base + 1, base + 2, base + 3, base + 4]
+    }
+
+    //Moves to the start of the first block that may hold a value >= target, if it lies ahead.
+    //Returns false, and moves to the end, if no block does.
+    fn skip_blocks(&mut self, target: u32) -> bool {
+        if self.blocks.is_empty() {
+            return true;
+        }
+
+        let block = self
+            .blocks
+            .partition_point(|header| header.last_value < target);
+        if block == self.blocks.len() {
+            self.descriptor_index = self.byte_stream.len();
+            self.value_index = self.len;
+            return false;
+        }
+
+        let header = self.blocks[block];
+        if header.byte_offset as usize > self.descriptor_index {
+            self.descriptor_index = header.byte_offset as usize;
+            self.last_top = self.blocks[block - 1].last_value;
+            self.value_index = block as u32 * self.block_len;
+        }
+        true
+    }
+
+    fn take_from_chunk(&mut self, target: u32) -> Option<u32> {
+        let pos = self.chunk_pos
+            + self.chunk[self.chunk_pos..self.chunk_len]
+                .iter()
+                .position(|val| *val >= target)?;
+        self.chunk_pos = pos + 1;
+        Some(self.chunk[pos])
+    }
+}
+
+impl<'a, 'b> Iterator for Iter<'a, 'b> {
+    type Item = [u32; 4];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chunk_pos = 0;
+        self.chunk_len = 0;
+        if self.descriptor_index >= self.byte_stream.len() {
+            return None;
+        }
+        self.value_index += 4;
+
+        if self.run_escapes {
+            if let Some(end) = escape_end(self.byte_stream, self.descriptor_index) {
+                return Some(self.next_in_run(end));
+            }
+        }
+
+        let descriptor = self.byte_stream[self.descriptor_index];
+
+        let desc_entry = self.descriptor_table.get_entry_for_descriptor(descriptor);
+
+        //If there arent 16 more bytes to take
+        if self.descriptor_index + 17 >= self.byte_stream.len() {
+            let chunk_byte_stream = &self.byte_stream[self.descriptor_index + 1..];
+            self.descriptor_index += (desc_entry.length + 1) as usize;
+            let mut delta_chunk = decode_chunk_safe_non_simd(descriptor, chunk_byte_stream);
+            if !self.absolute {
+                delta_chunk_to_value_chunk(&mut delta_chunk, self.last_top);
+            }
+            self.last_top = delta_chunk[3];
+            return Some(delta_chunk);
+        }
+
+        /*
+        let chunk_addr = ptr::addr_of!(self.byte_stream[self.descriptor_index + 1]);
+        let mut delta_chunk = self.descriptor_table.decode_chunk_by_address(chunk_addr, &desc_entry.shuffle_sequence);
+        */
+
+        let chunk = <&[u8; 16]>::try_from(
+            &self.byte_stream[self.descriptor_index + 1..self.descriptor_index + 17],
+        )
+        .unwrap();
+        let mut delta_chunk = self
+            .descriptor_table
+            .decode_chunk(chunk, &desc_entry.shuffle_sequence);
+
+        self.descriptor_index += (desc_entry.length + 1) as usize;
+
+        if !self.absolute {
+            delta_chunk_to_value_chunk(&mut delta_chunk, self.last_top);
+        }
+        self.last_top = delta_chunk[3];
+
+        Some(delta_chunk)
+    }
+}
+
+//Yields the values of the chunks one at a time. The stored length decides where the iterator ends,
+//so the padding of the final group is never returned.
+pub struct ValueIter<'a, 'b> {
+    chunks: Iter<'a, 'b>,
+    chunk: [u32; 4],
+    chunk_pos: usize,
+    //Byte offset of the descriptor of the current chunk, and the value its deltas are added to
+    chunk_offset: usize,
+    chunk_base: u32,
+    remaining: usize,
+}
+
+impl ValueIter<'_, '_> {
+    //Saves the position, so rewind can return to it without decoding from the start.
+    //Inside a chunk, the mark points at the chunk, which is decoded again on rewind.
+    pub fn mark(&self) -> CursorMark {
+        let ordinal = (self.chunks.len() - self.remaining) as u32;
+        if self.chunk_pos == 4 {
+            CursorMark {
+                byte_offset: self.chunks.descriptor_index,
+                last_value: self.chunks.last_top,
+                ordinal,
+            }
+        } else {
+            CursorMark {
+                byte_offset: self.chunk_offset,
+                last_value: self.chunk_base,
+                ordinal,
+            }
+        }
+    }
+
+    //Returns the first value >= target at or after the current position. Once the current chunk
+    //is used up, blocks that end below the target are skipped with the block headers.
+    pub fn next_geq(&mut self, target: u32) -> Option<u32> {
+        assert!(!self.chunks.absolute, "absolute values are not sorted");
+        while self.chunk_pos < 4 {
+            let val = self.next()?;
+            if val >= target {
+                return Some(val);
+            }
+        }
+
+        if !self.chunks.skip_blocks(target) {
+            self.remaining = 0;
+            return None;
+        }
+        if !self.chunks.run_escapes {
+            self.remaining = self.chunks.len() - self.chunks.value_index as usize;
+            return self.find(|val| *val >= target);
+        }
+
+        //One group at a time, so every escaped run on the way is skipped
+        loop {
+            self.chunks.skip_run(target);
+            self.remaining = self.chunks.len() - self.chunks.value_index as usize;
+            for _ in 0..4 {
+                let val = self.next()?;
+                if val >= target {
+                    return Some(val);
+                }
+            }
+        }
+    }
+
+    //Continues from a position saved by mark, before or after the current one
+    pub fn rewind(&mut self, mark: CursorMark) {
+        let chunk_pos = mark.ordinal as usize % 4;
+        self.chunks.descriptor_index = mark.byte_offset;
+        self.chunks.last_top = mark.last_value;
+        self.chunks.value_index = mark.ordinal - chunk_pos as u32;
+        self.chunk_pos = 4;
+        self.remaining = self.chunks.len() - mark.ordinal as usize;
+
+        if chunk_pos > 0 {
+            self.chunk_offset = mark.byte_offset;
+            self.chunk_base = mark.last_value;
+            if let Some(chunk) = self.chunks.next() {
+                self.chunk = chunk;
+                self.chunk_pos = chunk_pos;
+            }
+        }
+    }
+}
+
+impl Iterator for ValueIter<'_, '_> {
+    type Item = u32;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        if self.chunk_pos == 4 {
+            self.chunk_offset = self.chunks.descriptor_index;
+            self.chunk_base = self.chunks.last_top;
+            self.chunk = self.chunks.next()?;
+            self.chunk_pos = 0;
+        }
+
+        let val = self.chunk[self.chunk_pos];
+        self.chunk_pos += 1;
+        self.remaining -= 1;
+        Some(val)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for ValueIter<'_, '_> {}
+
+pub struct IterU16<'a, 'b> {
+    descriptor_table: &'b DescriptorTable,
+    byte_stream: &'a [u8],
+    descriptor_index: usize,
+    last_top: u16,
+    absolute: bool,
+}
+
+impl IterU16<'_, '_> {
+    //Decodes the next chunk as four u16 deltas.
+    //Past the end of the stream, the deltas are 0.
+    #[inline(always)]
+    fn next_delta_chunk(&mut self) -> [u16; 4] {
+        if self.descriptor_index >= self.byte_stream.len() {
+            return [0; 4];
+        }
+
+        let descriptor = self.byte_stream[self.descriptor_index];
+        let desc_entry = self.descriptor_table.get_entry_for_descriptor(descriptor);
+
+        //If there arent 16 more bytes to take
+        let delta_chunk = if self.descriptor_index + 17 >= self.byte_stream.len() {
+            let chunk_byte_stream = &self.byte_stream[self.descriptor_index + 1..];
+            let chunk = decode_chunk_safe_non_simd(descriptor, chunk_byte_stream);
+            chunk.map(|delta| delta as u16)
+        } else {
+            let chunk = <&[u8; 16]>::try_from(
+                &self.byte_stream[self.descriptor_index + 1..self.descriptor_index + 17],
+            )
+            .unwrap();
+            let shuffle_sequence = self
+                .descriptor_table
+                .get_shuffle_u16_for_descriptor(descriptor);
+            let shuffled = self
+                .descriptor_table
+                .shuffle_chunk(chunk, &shuffle_sequence);
+            [0, 2, 4, 6].map(|i| u16::from_le_bytes([shuffled[i], shuffled[i + 1]]))
+        };
+
+        self.descriptor_index += (desc_entry.length + 1) as usize;
+        delta_chunk
+    }
+}
+
+impl Iterator for IterU16<'_, '_> {
+    type Item = [u16; 8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.descriptor_index >= self.byte_stream.len() {
+            return None;
+        }
+
+        //Two chunks of deltas, followed by a prefix sum over the 8 values
+        let low = self.next_delta_chunk();
+        let high = self.next_delta_chunk();
+        let mut deltas = [0; 8];
+        deltas[..4].copy_from_slice(&low);
+        deltas[4..].copy_from_slice(&high);
+
+        if self.absolute {
+            return Some(deltas);
+        }
+        let values = prefix_sum_u16(deltas, self.last_top);
+        self.last_top = values[7];
+        Some(values)
+    }
+}
+
+//Turns the gaps of a chunk into values, as a running sum from last_top
+#[inline(always)]
+pub fn delta_chunk_to_value_chunk(delta_chunk: &mut [u32; 4], last_top: u32) {
+    delta_chunk[0] += last_top;
+    delta_chunk[1] += delta_chunk[0];
+    delta_chunk[2] += delta_chunk[1];
+    delta_chunk[3] += delta_chunk[2];
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, ptr, sync::Arc, thread};
+
+    use itertools::Itertools;
+    use rand::Rng;
+
+    use crate::{
+        codec::GapMode, error::CodecError, frame::FrameError, varint_gb::descriptor_length_i,
+    };
+
+    use super::{
+        auto_block_len, deltas_to_values, lower_bound_simd, Backend, DecodeContext,
+        DescriptorTable, ListHandle, VarintGB, VarintGBFactory, BLOCK_LEN, MIN_ESCAPED_RUN,
+    };
+
+    #[test]
+    fn test_of_sequence() {
+        let no_of_inserts = 100000;
+        let mut reference_vector = Vec::new();
+        let mut seq_factory = VarintGBFactory::new();
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..no_of_inserts {
+            reference_vector.push(rng.gen_range(1..no_of_inserts) as u32);
+        }
+
+        reference_vector.sort();
+        reference_vector = reference_vector.iter().unique().copied().collect();
+
+        for val in reference_vector.iter() {
+            seq_factory.push_int(*val);
+        }
+        let seq = seq_factory.into_varint_gb();
+
+        let shuffle_table = DescriptorTable::new();
+
+        let values: Vec<u32> = seq.iter_values(&shuffle_table).collect();
+        assert_eq!(values, reference_vector);
+        //println!("{},{}", seq_values.len(), reference_vector.len());
+    }
+
+    #[test]
+    fn test_iter_values() {
+        let shuffle_table = DescriptorTable::new();
+
+        //Every remainder of len modulo 4, so the final group holds 1 to 4 real values
+        for len in 0..=9 {
+            let reference_vector: Vec<u32> = (1..=len).map(|val| val * 1000).collect();
+            for mut seq_factory in [VarintGBFactory::new(), VarintGBFactory::new_block_aligned()] {
+                for val in reference_vector.iter() {
+                    seq_factory.push_int(*val);
+                }
+                let seq = seq_factory.into_varint_gb();
+
+                let mut iter = seq.iter_values(&shuffle_table);
+                assert_eq!(iter.len(), len as usize);
+                assert_eq!(iter.next(), reference_vector.first().copied());
+                assert_eq!(iter.len(), (len as usize).saturating_sub(1));
+
+                let values: Vec<u32> = seq.iter_values(&shuffle_table).collect();
+                assert_eq!(values, reference_vector);
+                assert_eq!(
+                    seq.iter_values(&shuffle_table).last(),
+                    reference_vector.last().copied()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_value_iter_next_geq() {
+        let shuffle_table = DescriptorTable::new();
+        let reference_vector: Vec<u32> = (1..=1001).map(|val| val * 37).collect();
+
+        for mut seq_factory in [VarintGBFactory::new(), VarintGBFactory::new_block_aligned()] {
+            for val in reference_vector.iter() {
+                seq_factory.push_int(*val);
+            }
+            let seq = seq_factory.into_varint_gb();
+
+            let mut iter = seq.iter_values(&shuffle_table);
+            assert_eq!(iter.next_geq(0), Some(37));
+            assert_eq!(iter.next_geq(38), Some(74));
+            assert_eq!(iter.next(), Some(111));
+            assert_eq!(iter.next_geq(37 * 700 + 1), Some(37 * 701));
+            assert_eq!(iter.len(), 300);
+            assert_eq!(iter.next(), Some(37 * 702));
+            //Seeking backwards does not go back
+            assert_eq!(iter.next_geq(37 * 10), Some(37 * 703));
+            assert_eq!(iter.next_geq(37 * 1001), Some(37 * 1001));
+            assert_eq!(iter.next_geq(37 * 1001), None);
+            assert_eq!(iter.len(), 0);
+
+            let mut iter = seq.iter_values(&shuffle_table);
+            assert_eq!(iter.next_geq(37 * 1001 + 1), None);
+            assert_eq!(iter.next(), None);
+        }
+    }
+
+    #[test]
+    fn test_mark_rewind() {
+        let shuffle_table = DescriptorTable::new();
+        let reference_vector: Vec<u32> = (1..=301).map(|val| val * val).collect();
+
+        for mut seq_factory in [VarintGBFactory::new(), VarintGBFactory::new_block_aligned()] {
+            for val in reference_vector.iter() {
+                seq_factory.push_int(*val);
+            }
+            let seq = seq_factory.into_varint_gb();
+
+            //Marks at and inside chunks, and at the end
+            let mut iter = seq.iter_values(&shuffle_table);
+            let mut marks = Vec::new();
+            for ordinal in 0..=reference_vector.len() {
+                marks.push(iter.mark());
+                assert_eq!(marks[ordinal].ordinal, ordinal as u32);
+                iter.next();
+            }
+
+            for ordinal in [300, 0, 5, 130, 4, 301, 2] {
+                iter.rewind(marks[ordinal]);
+                assert_eq!(iter.len(), reference_vector.len() - ordinal);
+                assert_eq!(iter.mark(), marks[ordinal]);
+                let rest: Vec<u32> = iter.by_ref().take(10).collect();
+                let end = (ordinal + 10).min(reference_vector.len());
+                assert_eq!(rest, reference_vector[ordinal..end]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_get() {
+        let shuffle_table = DescriptorTable::new();
+        let reference_vector: Vec<u32> = (1..=301).map(|val| val * val).collect();
+
+        for mut seq_factory in [
+            VarintGBFactory::new(),
+            VarintGBFactory::new_block_aligned(),
+            VarintGBFactory::new_with_block_len(4),
+        ] {
+            for val in reference_vector.iter() {
+                seq_factory.push_int(*val);
+            }
+            let seq = seq_factory.into_varint_gb();
+
+            for (index, val) in reference_vector.iter().enumerate() {
+                assert_eq!(seq.get(index, &shuffle_table), Some(*val));
+            }
+            assert_eq!(seq.get(301, &shuffle_table), None);
+            assert_eq!(seq.first(), Some(1));
+
+            for (start, end) in [
+                (0, 301),
+                (127, 131),
+                (128, 256),
+                (250, 400),
+                (5, 5),
+                (400, 500),
+            ] {
+                let end_in_seq = end.min(reference_vector.len());
+                let expected = reference_vector.get(start..end_in_seq).unwrap_or(&[]);
+                assert_eq!(seq.get_range(start..end, &shuffle_table), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_copy_range_compressed() {
+        let shuffle_table = DescriptorTable::new();
+        let mut rng = rand::thread_rng();
+        let mut reference_vector = Vec::new();
+        let mut value = 0;
+        //Every gap width, without overflowing u32
+        for _ in 0..201 {
+            value += [0, 1, 200, 70000, 1 << 24][rng.gen_range(0..5)];
+            reference_vector.push(value);
+        }
+        reference_vector.extend(value + 1..=value + 100);
+        let top = *reference_vector.last().unwrap();
+
+        for make_factory in [
+            VarintGBFactory::new,
+            VarintGBFactory::new_block_aligned,
+            VarintGBFactory::new_with_run_escapes,
+        ] {
+            let mut seq_factory = make_factory();
+            for val in reference_vector.iter() {
+                seq_factory.push_int(*val);
+            }
+            let seq = seq_factory.into_varint_gb();
+
+            let mut windows = vec![
+                (0, u32::MAX),
+                (0, 0),
+                (top, top),
+                (top + 1, u32::MAX),
+                (5, 4),
+            ];
+            for _ in 0..50 {
+                let from = reference_vector[rng.gen_range(0..reference_vector.len())];
+                windows.push((from, from.saturating_add(rng.gen_range(0..1 << 26))));
+                windows.push((from + 1, from + rng.gen_range(1..1 << 20)));
+            }
+
+            for (from, to) in windows {
+                let copy = seq.copy_range_compressed(from, to, &shuffle_table);
+                let expected: Vec<u32> = reference_vector
+                    .iter()
+                    .copied()
+                    .filter(|val| (from..=to).contains(val))
+                    .collect();
+                assert_eq!(copy.validate(), Ok(()));
+                assert_eq!(copy.get_values(&shuffle_table), expected);
+
+                //The copied gaps are the bytes a new encoding writes
+                let mut fresh_factory = make_factory();
+                for val in expected.iter() {
+                    fresh_factory.push_int(*val);
+                }
+                let fresh = fresh_factory.into_varint_gb();
+                assert_eq!(copy.byte_stream, fresh.byte_stream);
+                assert_eq!(copy.blocks(), fresh.blocks());
+                assert_eq!(copy.stats(), fresh.stats());
+                assert_eq!(copy.top(), fresh.top());
+            }
+        }
+    }
+
+    #[test]
+    fn test_handles() {
+        let shuffle_table = DescriptorTable::new();
+        let lists: Vec<Vec<u32>> = (0..20)
+            .map(|list| (1..=list * 13).map(|val| val * (list + 1)).collect())
+            .collect();
+
+        let mut buffer = Vec::new();
+        let mut handles = Vec::new();
+        for list in lists.iter() {
+            let mut seq_factory = VarintGBFactory::new_in(buffer);
+            for val in list.iter() {
+                seq_factory.push_int(*val);
+            }
+            let handle;
+            (buffer, handle) = seq_factory.into_handle();
+            handles.push(handle);
+        }
+        assert_eq!(handles[0].offset, 0);
+        assert_eq!(
+            handles
+                .iter()
+                .map(|handle| handle.byte_len as usize)
+                .sum::<usize>(),
+            buffer.len()
+        );
+
+        for (list, handle) in lists.iter().zip(handles.iter()) {
+            let seq = VarintGB::from_handle(&buffer, *handle).unwrap();
+            assert!(seq.iter_values(&shuffle_table).eq(list.iter().copied()));
+        }
+
+        //A factory from new_in still builds a sequence of its own
+        let mut seq_factory = VarintGBFactory::new_in(buffer.clone());
+        seq_factory.push_int(5);
+        let seq = seq_factory.into_varint_gb();
+        assert_eq!(seq.get_values(&shuffle_table), [5]);
+
+        let handle = handles[19];
+        let past_end = ListHandle {
+            byte_len: handle.byte_len + 1,
+            ..handle
+        };
+        assert!(VarintGB::from_handle(&buffer, past_end).is_err());
+        let too_long = ListHandle {
+            len: handle.len + 5,
+            ..handle
+        };
+        assert!(VarintGB::from_handle(&buffer, too_long).is_err());
+    }
+
+    #[test]
+    fn test_run_aware_decoding() {
+        let shuffle_table = DescriptorTable::new();
+        let mut rng = rand::thread_rng();
+
+        //Clustered data, where every gap fits in one byte, followed by a few large gaps
+        let mut reference_vector = Vec::new();
+        let mut value = 0;
+        for _ in 0..10003 {
+            value += rng.gen_range(1..256);
+            reference_vector.push(value);
+        }
+        for _ in 0..7 {
+            value += rng.gen_range(1..1 << 24);
+            reference_vector.push(value);
+        }
+
+        let mut seq_factory = VarintGBFactory::new();
+        for val in reference_vector.iter() {
+            seq_factory.push_int(*val);
+        }
+        let seq = seq_factory.into_varint_gb();
+
+        assert_eq!(seq.get_values_run_aware(&shuffle_table), reference_vector);
+    }
+
+    #[test]
+    fn test_u16_decoding() {
+        let shuffle_table = DescriptorTable::new();
+        let mut rng = rand::thread_rng();
+
+        //Gaps of both one and two bytes, and a length that is not a multiple of 8
+        let mut reference_vector = Vec::new();
+        let mut value = 0;
+        for _ in 0..301 {
+            value += rng.gen_range(1..400);
+            reference_vector.push(value);
+        }
+
+        let mut seq_factory = VarintGBFactory::new();
+        for val in reference_vector.iter() {
+            seq_factory.push_int(*val);
+        }
+        let seq = seq_factory.into_varint_gb();
+        assert!(seq.fits_u16());
+
+        let values = seq.get_values_u16(&shuffle_table).unwrap();
+        let reference_u16: Vec<u16> = reference_vector.iter().map(|v| *v as u16).collect();
+        assert_eq!(values, reference_u16);
+
+        let mut seq_factory = VarintGBFactory::new();
+        seq_factory.push_int(70000);
+        let seq = seq_factory.into_varint_gb();
+        assert!(seq.iter_u16(&shuffle_table).is_none());
+    }
+
+    #[test]
+    fn test_shared_table_across_threads() {
+        let mut seq_factory = VarintGBFactory::new();
+        for val in 1..=1000 {
+            seq_factory.push_int(val * 3);
+        }
+        let seq = Arc::new(seq_factory.into_varint_gb());
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let seq = Arc::clone(&seq);
+                let shuffle_table = DescriptorTable::shared();
+                thread::spawn(move || seq.get_values(&shuffle_table))
+            })
+            .collect();
+
+        let reference_vector: Vec<u32> = (1..=1000).map(|val| val * 3).collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), reference_vector);
+        }
+        assert!(Arc::ptr_eq(
+            &DescriptorTable::shared(),
+            &DescriptorTable::shared()
+        ));
+    }
+
+    #[test]
+    fn test_absolute() {
+        let shuffle_table = DescriptorTable::new();
+        let mut rng = rand::thread_rng();
+
+        //Repeats, values that go down, zeros and every width, then random values
+        let mut reference_vector = vec![5, 5, 0, 1, 300, 2, 70000, 0, u32::MAX, 3, 3];
+        for _ in 0..1000 {
+            let bits = rng.gen_range(0..32);
+            reference_vector.push(rng.gen_range(0..=u32::MAX >> bits));
+        }
+
+        for len in [0, 1, 3, 4, 11, reference_vector.len()] {
+            let values = &reference_vector[..len];
+            let mut seq_factory = VarintGBFactory::new_absolute();
+            for val in values.iter() {
+                seq_factory.push_int(*val);
+            }
+            let seq = seq_factory.into_varint_gb();
+            assert_eq!(seq.gap_mode(), GapMode::Absolute);
+            assert_eq!(seq.validate(), Ok(()));
+
+            assert_eq!(seq.get_values(&shuffle_table), values);
+            assert_eq!(seq.get_values_run_aware(&shuffle_table), values);
+            assert_eq!(
+                seq.iter_values(&shuffle_table).collect::<Vec<u32>>(),
+                values
+            );
+            let mut output = Vec::new();
+            seq.decode_into(&mut output, &shuffle_table);
+            assert_eq!(output, values);
+            if len > 2 {
+                assert_eq!(seq.get(2, &shuffle_table), Some(0));
+            }
+
+            let loaded = VarintGB::from_bytes(&seq.to_bytes()).unwrap();
+            assert_eq!(loaded.gap_mode(), GapMode::Absolute);
+            assert_eq!(loaded.get_values(&shuffle_table), values);
+        }
+
+        //Small absolute values decode as u16, although they go down
+        let small_values = [500, 3, 3, 65535, 0, 17, 9, 1, 2];
+        let mut seq_factory = VarintGBFactory::new_absolute();
+        for val in small_values.iter() {
+            seq_factory.push_int(*val);
+        }
+        let seq = seq_factory.into_varint_gb();
+        let expected: Vec<u16> = small_values.iter().map(|val| *val as u16).collect();
+        assert_eq!(seq.get_values_u16(&shuffle_table).unwrap(), expected);
+    }
+
+    fn absolute_seq() -> VarintGB<'static> {
+        let mut seq_factory = VarintGBFactory::new_absolute();
+        for val in [100, 5, 3] {
+            seq_factory.push_int(val);
+        }
+        seq_factory.into_varint_gb()
+    }
+
+    //Seeking by value needs sorted values
+    #[test]
+    #[should_panic(expected = "absolute values are not sorted")]
+    fn test_absolute_decode_window() {
+        let mut output = Vec::new();
+        absolute_seq().decode_window(0, 10, &DescriptorTable::new(), &mut output);
+    }
+
+    #[test]
+    #[should_panic(expected = "absolute values are not sorted")]
+    fn test_absolute_next_geq() {
+        let shuffle_table = DescriptorTable::new();
+        absolute_seq().iter_values(&shuffle_table).next_geq(4);
+    }
+
+    #[test]
+    #[should_panic(expected = "absolute values are not sorted")]
+    fn test_absolute_chunk_next_geq() {
+        let shuffle_table = DescriptorTable::new();
+        absolute_seq().iter(&shuffle_table).next_geq(4);
+    }
+
+    #[test]
+    fn test_repeated_values() {
+        let shuffle_table = DescriptorTable::new();
+
+        //Gaps of 0 in the final group are values, not the end of the stream
+        for len in [1, 5, 6, 7, 8, 9, 300] {
+            let reference_vector: Vec<u32> = (0..len).map(|val| val.min(5)).collect();
+            let mut seq_factory = VarintGBFactory::new();
+            for val in reference_vector.iter() {
+                seq_factory.push_int(*val);
+            }
+            let seq = seq_factory.into_varint_gb();
+            assert_eq!(seq.gap_mode(), GapMode::NonStrict);
+            assert_eq!(seq.get_values(&shuffle_table), reference_vector);
+            assert_eq!(seq.get_values_run_aware(&shuffle_table), reference_vector);
+        }
+    }
+
+    #[test]
+    fn test_run_escapes() {
+        let shuffle_table = DescriptorTable::new();
+
+        //Repeated values, a run that starts inside a group, large gaps, and a run that ends the list
+        let mut mixed = vec![0, 0];
+        mixed.extend(1..=300);
+        mixed.extend((1..=50).map(|val| 300 + val * 1000));
+        mixed.push(50300);
+        mixed.extend(50301..=50400);
+
+        for (reference_vector, expected_runs) in [
+            (mixed, (2, 296 + 96)),
+            ((1..=100).collect(), (1, 100)),
+            ((1..=MIN_ESCAPED_RUN).collect(), (1, 64)),
+            ((1..MIN_ESCAPED_RUN).collect(), (0, 0)),
+            ((5..=68).collect(), (0, 0)),
+            (vec![], (0, 0)),
+        ] {
+            let mut seq_factory = VarintGBFactory::new_with_run_escapes();
+            let mut plain_factory = VarintGBFactory::new();
+            for val in reference_vector.iter() {
+                seq_factory.push_int(*val);
+                plain_factory.push_int(*val);
+            }
+            let seq = seq_factory.into_varint_gb();
+            let plain = plain_factory.into_varint_gb();
+
+            assert!(seq.has_run_escapes());
+            assert_eq!(seq.validate(), Ok(()));
+            assert_eq!(seq.escaped_runs(), expected_runs);
+            assert_eq!(plain.escaped_runs(), (0, 0));
+            if expected_runs.0 > 0 {
+                assert!(seq.byte_stream.len() < plain.byte_stream.len());
+            } else {
+                assert_eq!(seq.byte_stream, plain.byte_stream);
+            }
+            assert_eq!(seq.first(), reference_vector.first().copied());
+            assert_eq!(seq.top(), plain.top());
+
+            assert_eq!(seq.get_values(&shuffle_table), reference_vector);
+            assert_eq!(seq.get_values_run_aware(&shuffle_table), reference_vector);
+            assert_eq!(seq.get_values_u16(&shuffle_table), None);
+            let mut output = vec![9];
+            seq.decode_into(&mut output, &shuffle_table);
+            assert_eq!(output[1..], reference_vector);
+            assert_eq!(
+                DecodeContext::new(&shuffle_table).decode(&seq),
+                reference_vector
+            );
+            let chunks: Vec<u32> = seq.iter(&shuffle_table).flatten().collect();
+            assert_eq!(chunks[..reference_vector.len()], reference_vector);
+
+            for (index, val) in reference_vector.iter().enumerate() {
+                assert_eq!(seq.get(index, &shuffle_table), Some(*val));
+            }
+            for (start, end) in [(0, 500), (3, 7), (100, 301), (298, 310)] {
+                let end_in_seq = end.min(reference_vector.len());
+                let expected = reference_vector.get(start..end_in_seq).unwrap_or(&[]);
+                assert_eq!(seq.get_range(start..end, &shuffle_table), expected);
+                output.clear();
+                seq.decode_range_into(start..end, &mut output, &shuffle_table);
+                assert_eq!(output, expected);
+            }
+
+            //Seeking into, within and past a run, from the start and from the previous target
+            let mut iter = seq.iter_values(&shuffle_table);
+            let mut chunk_iter = seq.iter(&shuffle_table);
+            let mut position = 0;
+            for target in [
+                0, 1, 2, 3, 150, 151, 298, 299, 5000, 50302, 50350, 50400, 50401,
+            ] {
+                let expected = reference_vector.iter().copied().find(|val| *val >= target);
+                assert_eq!(seq.iter_values(&shuffle_table).next_geq(target), expected);
+                assert_eq!(seq.iter(&shuffle_table).next_geq(target), expected);
+
+                let skipped = reference_vector[position..]
+                    .iter()
+                    .position(|val| *val >= target);
+                let expected = skipped.map(|skipped| reference_vector[position + skipped]);
+                position = skipped.map_or(reference_vector.len(), |skipped| position + skipped + 1);
+                assert_eq!(iter.next_geq(target), expected);
+                assert_eq!(chunk_iter.next_geq(target), expected);
+            }
+
+            //Marks inside runs rewind without decoding the run from its start
+            let mut iter = seq.iter_values(&shuffle_table);
+            let mut marks = Vec::new();
+            for _ in 0..=reference_vector.len() {
+                marks.push(iter.mark());
+                iter.next();
+            }
+            for ordinal in [0, 5, 99, 150, 4, 300, 2, reference_vector.len()] {
+                let Some(mark) = marks.get(ordinal) else {
+                    continue;
+                };
+                iter.rewind(*mark);
+                let rest: Vec<u32> = iter.by_ref().take(10).collect();
+                let end = (ordinal + 10).min(reference_vector.len());
+                assert_eq!(rest, reference_vector[ordinal..end]);
+            }
+
+            let bytes = seq.to_bytes();
+            let loaded = VarintGB::from_slice(&bytes).unwrap();
+            assert!(loaded.has_run_escapes());
+            assert_eq!(loaded.get_values(&shuffle_table), reference_vector);
+        }
 
-        self.index_in_chunk = (self.index_in_chunk + 1) % 4;
+        //An escape must continue the values before it by whole groups
+        let mut seq_factory = VarintGBFactory::new_with_run_escapes();
+        for val in 1..=100 {
+            seq_factory.push_int(val);
+        }
+        let mut seq = seq_factory.into_varint_gb();
+        assert_eq!(&seq.byte_stream[..], [0, 0, 100, 0, 0, 0]);
+        for end in [99, 101, 0] {
+            seq.byte_stream.to_mut()[2] = end;
+            assert_eq!(seq.validate(), Err(CodecError::InvalidEscape { offset: 0 }));
+        }
+        seq.byte_stream.to_mut()[2] = 100;
+        seq.run_escapes = false;
+        assert!(seq.validate().is_err());
     }
 
-    pub fn into_varint_gb(&mut self) -> VarintGB {
-        VarintGB {
-            byte_stream: std::mem::replace(&mut self.byte_stream, Vec::new()).into_boxed_slice(),
-            len: self.len,
+    #[test]
+    fn test_decode_into() {
+        let mut rng = rand::thread_rng();
+        let mut reference_vector = Vec::new();
+        let mut value = 0;
+        for _ in 0..1001 {
+            let bits = rng.gen_range(1..30);
+            value += rng.gen_range(1..1 << bits) >> 6;
+            reference_vector.push(value);
         }
-    }
-}
 
-pub fn decode_chunk(chunk: &[u8; 16], shuffle_sequence: __m128i) -> [u32; 4] {
-    let unshufled_array: [u32; 4];
-    //let four_numbers: [u8; 16] = chunk[..16].try_into().ok().unwrap();
+        for mut seq_factory in [
+            VarintGBFactory::new(),
+            VarintGBFactory::new_block_aligned(),
+            VarintGBFactory::new_with_block_len(4),
+        ] {
+            for val in reference_vector.iter() {
+                seq_factory.push_int(*val);
+            }
+            let seq = seq_factory.into_varint_gb();
 
-    unsafe {
-        let four_numbers_simd_vec: __m128i = std::mem::transmute(*chunk);
-        let unshufled_vector = _mm_shuffle_epi8(four_numbers_simd_vec, shuffle_sequence);
-        unshufled_array = std::mem::transmute(unshufled_vector);
-        //let output_address_pointer = std::mem::transmute(output_address);
-        //_mm_storeu_si128(output_address_pointer, unshufled_vector);
-    }
+            for backend in [Backend::Scalar, Backend::Ssse3, Backend::Avx2] {
+                if !backend.is_supported() {
+                    continue;
+                }
+                let shuffle_table = DescriptorTable::with_backend(backend);
 
-    unshufled_array
-}
+                let mut output = Vec::new();
+                seq.decode_into(&mut output, &shuffle_table);
+                assert_eq!(output, reference_vector);
 
-#[inline(always)]
-pub fn decode_chunk_by_address(chunk_addr: *mut __m128i, shuffle_sequence: __m128i) -> [u32; 4] {
-    unsafe {
-        let four_numbers_simd_vec: __m128i = _mm_loadu_si128(chunk_addr);
-        let unshufled_vector = _mm_shuffle_epi8(four_numbers_simd_vec, shuffle_sequence);
-        return std::mem::transmute(unshufled_vector);
-    }
-}
-
-#[target_feature(enable = "sse3")]
-pub unsafe fn decode_chunk_to(
-    chunk: &[u8; 16],
-    shuffle_sequence: __m128i,
-    destination_vec: &mut Vec<__m128i>,
-) {
-    unsafe {
-        let four_numbers_simd_vec: __m128i = std::mem::transmute(*chunk);
-        destination_vec.push(_mm_shuffle_epi8(four_numbers_simd_vec, shuffle_sequence));
-    }
-}
-
-#[cfg(all(
-    any(target_arch = "x86", target_arch = "x86_64"),
-    target_feature = "avx2"
-))]
-pub unsafe fn decode_chunk_to_address(
-    chunk: &[u8; 16],
-    shuffle_sequence: __m128i,
-    destination_address: *mut __m128i,
-) {
-    unsafe {
-        let four_numbers_simd_vec: __m128i = std::mem::transmute(*chunk);
-        let unshuffled = _mm_shuffle_epi8(four_numbers_simd_vec, shuffle_sequence);
-        _mm_storeu_si128(destination_address, unshuffled);
-    }
-}
-
-#[cfg(all(
-    any(target_arch = "x86", target_arch = "x86_64"),
-    target_feature = "avx2"
-))]
-pub unsafe fn decode_chunk_by_address_to_address(
-    chunk_addr: *mut __m128i,
-    shuffle_sequence: __m128i,
-    destination_address: *mut __m128i,
-) {
-    use std::arch::x86_64::_mm_loadu_si128;
-
-    unsafe {
-        let four_numbers_simd_vec: __m128i = _mm_loadu_si128(chunk_addr);
-        let unshuffled = _mm_shuffle_epi8(four_numbers_simd_vec, shuffle_sequence);
-        _mm_storeu_si128(destination_address, unshuffled);
-    }
-}
-
-fn shuffle_sequence_from_descriptor(descriptor: u8) -> [i8; 16] {
-    let mut word_index = 0;
-    let mut shuffle_index = 0;
-    let mut shuffle = [0_i8; 16];
-
-    //For each word in the descriptor
-    for i in 0..4 {
-        //For each output byte corresponding to the word
-        let word_len = descriptor_length_i(descriptor, i);
-        for n in 0..4 {
-            if n < word_len {
-                shuffle[shuffle_index] = word_index;
-                word_index += 1;
-            } else {
-                shuffle[shuffle_index] = -1;
+                for (start, end) in [(0, 1001), (0, 3), (127, 131), (129, 900), (998, 2000)] {
+                    output.clear();
+                    seq.decode_range_into(start..end, &mut output, &shuffle_table);
+                    assert_eq!(output, reference_vector[start..end.min(1001)]);
+                }
+                output.clear();
+                seq.decode_range_into(1001..1005, &mut output, &shuffle_table);
+                assert!(output.is_empty());
             }
-            shuffle_index += 1;
         }
     }
 
-    shuffle
-}
-
-fn deltas_to_values(deltas: &[u32]) -> Vec<u32> {
-    let mut values = Vec::with_capacity(deltas.len());
-    let mut last = 0;
-    for delta in deltas.iter() {
-        if *delta == 0 {
-            continue;
+    #[test]
+    fn test_backends() {
+        let mut rng = rand::thread_rng();
+        let mut reference_vector = Vec::new();
+        let mut value = 0;
+        for _ in 0..1001 {
+            let bits = rng.gen_range(1..24);
+            value += rng.gen_range(1..1 << bits);
+            reference_vector.push(value);
         }
-        last += delta;
-        values.push(last);
-    }
-    values
-}
+        let mut seq_factory = VarintGBFactory::new_block_aligned();
+        for val in reference_vector.iter() {
+            seq_factory.push_int(*val);
+        }
+        let seq = seq_factory.into_varint_gb();
 
-fn descriptor_length_i(descriptor: u8, index: usize) -> u8 {
-    let mut mask = 0b00000011u8;
-    mask <<= index * 2;
-    let len = descriptor & mask;
-    (len >> (index * 2)) + 1
-}
+        let detected = Backend::detect();
+        assert_eq!(DescriptorTable::new().backend(), detected);
+
+        for backend in [Backend::Scalar, detected] {
+            let shuffle_table = DescriptorTable::with_backend(backend);
+            assert_eq!(seq.get_values(&shuffle_table), reference_vector);
+            assert_eq!(seq.get_values_run_aware(&shuffle_table), reference_vector);
+            let mut output = vec![7];
+            seq.decode_into(&mut output, &shuffle_table);
+            assert_eq!(output[1..], reference_vector);
+            let values: Vec<u32> = seq.iter_values(&shuffle_table).collect();
+            assert_eq!(values, reference_vector);
+            assert_eq!(
+                seq.decode_block(3, &shuffle_table),
+                reference_vector[3 * BLOCK_LEN..4 * BLOCK_LEN]
+            );
+        }
 
-fn descriptor_length_total(descriptor: u8) -> u8 {
-    let mut length = 0;
-    for i in 0..4 {
-        length += descriptor_length_i(descriptor, i);
+        let small_values: Vec<u32> = (1..=301).map(|val| val * 200).collect();
+        let mut seq_factory = VarintGBFactory::new();
+        for val in small_values.iter() {
+            seq_factory.push_int(*val);
+        }
+        let seq = seq_factory.into_varint_gb();
+        let small_u16: Vec<u16> = small_values.iter().map(|val| *val as u16).collect();
+        for backend in [Backend::Scalar, detected] {
+            let shuffle_table = DescriptorTable::with_backend(backend);
+            assert_eq!(seq.get_values_u16(&shuffle_table).unwrap(), small_u16);
+        }
     }
-    length
-}
-
-#[derive(Copy, Clone)]
-pub struct DescriptorEntry {
-    shuffle_sequence: __m128i,
-    length: u8,
-}
 
-pub struct DescriptorTable {
-    table: Vec<DescriptorEntry>,
-}
+    #[test]
+    fn test_descriptor_table_serialization() {
+        let shuffle_table = DescriptorTable::new();
+        let bytes = shuffle_table.to_bytes();
+        assert_eq!(bytes.len(), DescriptorTable::SERIALIZED_LEN);
 
-impl DescriptorTable {
-    pub fn new() -> Self {
-        let mut table = Vec::with_capacity(256);
+        let loaded_table = DescriptorTable::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded_table.to_bytes(), bytes);
         for descriptor in 0..=255 {
-            table.push(Self::create_entry_for_descriptor(descriptor))
+            assert_eq!(
+                loaded_table.get_length_for_descriptor(descriptor),
+                shuffle_table.get_length_for_descriptor(descriptor)
+            );
         }
 
-        DescriptorTable { table }
+        //A length past the chunk would move the unsafe iterator past the end of the stream
+        let mut corrupt = bytes.clone();
+        corrupt[32] = 200;
+        assert!(matches!(
+            DescriptorTable::from_bytes(&corrupt),
+            Err(CodecError::InvalidDescriptorEntry { descriptor: 0 })
+        ));
+
+        assert!(DescriptorTable::from_bytes(&bytes[1..]).is_err());
+        let mut too_long = bytes.clone();
+        too_long.push(0);
+        assert!(DescriptorTable::from_bytes(&too_long).is_err());
     }
 
-    fn create_entry_for_descriptor(descriptor: u8) -> DescriptorEntry {
-        let shf = unsafe { std::mem::transmute(shuffle_sequence_from_descriptor(descriptor)) };
-        let length = descriptor_length_total(descriptor);
-        DescriptorEntry {
-            shuffle_sequence: shf,
-            length,
+    #[test]
+    fn test_corrupted_descriptor_table() {
+        let bytes = DescriptorTable::new().to_bytes();
+        let entry_len = DescriptorTable::SERIALIZED_LEN / 256;
+        let corrupted = |offset: usize, byte: u8| {
+            let mut corrupt = bytes.clone();
+            corrupt[offset] = byte;
+            DescriptorTable::from_bytes(&corrupt).err()
+        };
+
+        for descriptor in 0..=255u8 {
+            let entry = descriptor as usize * entry_len;
+            let expected = Some(CodecError::InvalidDescriptorEntry { descriptor });
+            //Lengths outside the chunk, or that do not match the descriptor
+            for length in [0, 3, 17, 255, bytes[entry + 32] ^ 1] {
+                assert_eq!(corrupted(entry + 32, length), expected);
+            }
+            //Shuffle indices past 15, which pshufb reads by their low bits only
+            assert_eq!(corrupted(entry + 3, 16 + 3), expected);
+            assert_eq!(corrupted(entry + 16, 0x7F), expected);
         }
-    }
-
-    #[inline(always)]
-    pub fn get_entry_for_descriptor(&self, descriptor: u8) -> DescriptorEntry {
-        self.table[descriptor as usize]
-    }
 
-    pub fn get_shuffle_for_descriptor(&mut self, descriptor: u8) -> __m128i {
-        self.get_entry_for_descriptor(descriptor).shuffle_sequence
-    }
+        //Only the indices every backend reads the same are accepted, at any byte of the word
+        for offset in [33, 40, 47, 64] {
+            for index in 0..=255u8 {
+                let valid = index < 16 || index == 0xFF;
+                assert_eq!(corrupted(offset, index).is_none(), valid, "{index}");
+            }
+        }
 
-    #[allow(dead_code)]
-    pub fn get_length_for_descriptor(&mut self, descriptor: u8) -> u8 {
-        self.get_entry_for_descriptor(descriptor).length
+        //Cut off inside an entry
+        assert!(matches!(
+            DescriptorTable::from_bytes(&bytes[..100]),
+            Err(CodecError::Frame(FrameError::UnexpectedEnd {
+                offset: 0,
+                available: 100,
+                ..
+            }))
+        ));
     }
-}
 
-#[inline(always)]
-pub fn decode_chunk_safe_non_simd(descriptor: u8, byte_stream: &[u8]) -> [u32; 4] {
-    let mut chunk = [0; 4];
-    let mut index = 0;
-    for i in 0..4 {
-        let len = descriptor_length_i(descriptor, i);
-        let mut num = 0;
-        for pos in 0..len {
-            if index >= byte_stream.len() {
-                return chunk;
-            }
-            num += (byte_stream[index] as u32) << pos * 8;
-            index += 1;
+    #[test]
+    fn test_gap_stats() {
+        let mut seq_factory = VarintGBFactory::new();
+        //Gaps: 1, 1, 1, 300, 1, 70000, 1, 1
+        for val in [1, 2, 3, 303, 304, 70304, 70305, 70306] {
+            seq_factory.push_int(val);
         }
-        chunk[i] = num;
-    }
-    chunk
-}
+        let stats = seq_factory.into_varint_gb().stats();
 
-#[test]
-pub fn test_safe_decoder_non_simd() {
-    let byte_stream = [0b11111001, 0b11111111, 1, 1, 1];
-    let descriptor = 0b00000001u8;
-    let vals = decode_chunk_safe_non_simd(descriptor, &byte_stream);
-    for val in vals {
-        println!("{val}");
+        assert_eq!(stats.max_gap, 70000);
+        assert_eq!(stats.gap_one_runs, 3);
+        assert_eq!(stats.longest_gap_one_run, 3);
+        assert_eq!(stats.bytes_per_length, [6, 2, 3, 0]);
     }
 
-    let u16 = 0b1111100111111111;
-    let u16_2 = 0b1111111111111100;
-    println!("u16: {u16_2}");
-}
-pub struct IterUnsafe<'b> {
-    descriptor_table: &'b DescriptorTable,
-    src: *mut u8,
-    chunks_to_decode: u32,
-}
+    #[test]
+    fn test_block_aligned() {
+        let shuffle_table = DescriptorTable::new();
+        let mut rng = rand::thread_rng();
 
-impl<'a, 'b> Iterator for IterUnsafe<'b> {
-    type Item = [u32; 4];
+        let mut reference_vector = Vec::new();
+        let mut value = 0;
+        for _ in 0..3 * BLOCK_LEN + 5 {
+            value += rng.gen_range(1..100000);
+            reference_vector.push(value);
+        }
 
-    #[inline(always)]
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.chunks_to_decode < 1 {
-            return None;
+        let mut seq_factory = VarintGBFactory::new_block_aligned();
+        for val in reference_vector.iter() {
+            seq_factory.push_int(*val);
         }
+        let seq = seq_factory.into_varint_gb();
 
-        unsafe {
-            let descriptor: u8 = *self.src;
-            let chunk_addr = self.src.add(1) as *mut __m128i;
+        assert_eq!(seq.blocks().len(), 4);
+        assert_eq!(seq.get_values_run_aware(&shuffle_table), reference_vector);
 
-            let descriptor_entry = self.descriptor_table.get_entry_for_descriptor(descriptor);
-            let shf = descriptor_entry.shuffle_sequence;
+        let mut byte_offset = 0;
+        for (block, values) in reference_vector.chunks(BLOCK_LEN).enumerate() {
+            let header = seq.blocks()[block];
+            assert_eq!(header.byte_offset, byte_offset);
+            assert_eq!(header.last_value, *values.last().unwrap());
+            assert_eq!(seq.decode_block(block, &shuffle_table), values);
+            byte_offset += header.byte_len;
+        }
+        assert_eq!(byte_offset as usize, seq.byte_stream.len());
+    }
 
-            //println!("{}", chunk_addr as u32);
+    #[test]
+    fn test_block_len() {
+        let shuffle_table = DescriptorTable::new();
+        assert_eq!(auto_block_len(10), 16);
+        assert_eq!(auto_block_len(1000000), 1000);
 
-            let chunk = decode_chunk_by_address(chunk_addr, shf);
-            self.src = self.src.add(descriptor_entry.length as usize + 1);
-            self.chunks_to_decode -= 1;
-            return Some(chunk);
+        let reference_vector: Vec<u32> = (1..=10000).map(|val| val * 7).collect();
+        let mut seq_factory = VarintGBFactory::new_auto_block_len(reference_vector.len());
+        for val in reference_vector.iter() {
+            seq_factory.push_int(*val);
         }
+        let seq = seq_factory.into_varint_gb();
+        assert_eq!(seq.block_len(), 100);
+        assert_eq!(seq.blocks().len(), 100);
+
+        let block = seq.block_for_value(7 * 5050).unwrap();
+        assert_eq!(block, 50);
+        assert_eq!(seq.decode_block(block, &shuffle_table)[0], 7 * 5001);
+        assert_eq!(seq.block_for_value(7 * 10000), Some(99));
+        assert_eq!(seq.block_for_value(7 * 10000 + 1), None);
     }
-}
-pub struct Iter<'a, 'b> {
-    descriptor_table: &'b DescriptorTable,
-    byte_stream: &'a [u8],
-    len: u32,
-    descriptor_index: usize,
-    last_top: u32,
-}
-impl Iter<'_, '_> {
-    pub fn len(&self) -> usize {
-        self.len as usize
+
+    #[test]
+    fn test_next_geq() {
+        let shuffle_table = DescriptorTable::new();
+        let reference_vector: Vec<u32> = (1..=1001).map(|val| val * 7).collect();
+
+        for block_len in [0, 4, 128] {
+            let mut seq_factory = if block_len == 0 {
+                VarintGBFactory::new()
+            } else {
+                VarintGBFactory::new_with_block_len(block_len)
+            };
+            for val in reference_vector.iter() {
+                seq_factory.push_int(*val);
+            }
+            let seq = seq_factory.into_varint_gb();
+
+            let mut iter = seq.iter(&shuffle_table);
+            assert_eq!(iter.next_geq(0), Some(7));
+            assert_eq!(iter.next_geq(7), Some(14));
+            assert_eq!(iter.next_geq(7 * 300 + 1), Some(7 * 301));
+            assert_eq!(iter.next_geq(7 * 302), Some(7 * 302));
+            //next continues after the chunk of 7 * 301 and 7 * 302
+            assert_eq!(iter.next(), Some([7 * 305, 7 * 306, 7 * 307, 7 * 308]));
+            assert_eq!(iter.next_geq(7 * 1001), Some(7 * 1001));
+            assert_eq!(iter.next_geq(7 * 1001), None);
+
+            //Every value in order, by seeking to one past the last value found
+            let mut iter = seq.iter(&shuffle_table);
+            let mut values = Vec::new();
+            let mut target = 0;
+            while let Some(val) = iter.next_geq(target) {
+                values.push(val);
+                target = val + 1;
+            }
+            assert_eq!(values, reference_vector);
+
+            let mut iter = seq.iter(&shuffle_table);
+            assert_eq!(iter.next_geq(7 * 1001 + 1), None);
+        }
     }
-}
 
-impl<'a, 'b> Iterator for Iter<'a, 'b> {
-    type Item = [u32; 4];
+    #[test]
+    fn test_serialization() {
+        let shuffle_table = DescriptorTable::new();
+        let reference_vector: Vec<u32> = (1..=1001).map(|val| val * val).collect();
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.descriptor_index >= self.byte_stream.len() {
-            return None;
+        for block_aligned in [false, true] {
+            let mut seq_factory = if block_aligned {
+                VarintGBFactory::new_block_aligned()
+            } else {
+                VarintGBFactory::new()
+            };
+            for val in reference_vector.iter() {
+                seq_factory.push_int(*val);
+            }
+            let seq = seq_factory.into_varint_gb();
+            let bytes = seq.to_bytes();
+
+            let loaded = VarintGB::from_slice(&bytes).unwrap();
+            assert!(matches!(loaded.byte_stream, Cow::Borrowed(_)));
+            assert_eq!(
+                loaded.get_values_run_aware(&shuffle_table),
+                reference_vector
+            );
+            assert_eq!(loaded.blocks(), seq.blocks());
+            assert_eq!(loaded.stats(), seq.stats());
+
+            let mut written = Vec::new();
+            seq.write_to(&mut written).unwrap();
+            assert_eq!(written, bytes);
+            let loaded = VarintGB::from_reader(&mut written.as_slice()).unwrap();
+            assert_eq!(
+                loaded.get_values_run_aware(&shuffle_table),
+                reference_vector
+            );
         }
 
-        let descriptor = self.byte_stream[self.descriptor_index];
+        let mut seq_factory = VarintGBFactory::new();
+        for val in 1..=10 {
+            seq_factory.push_int(val);
+        }
+        let bytes = seq_factory.into_varint_gb().to_bytes();
 
-        let desc_entry = self.descriptor_table.get_entry_for_descriptor(descriptor);
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = 4;
+        assert_eq!(
+            VarintGB::from_slice(&wrong_version).err(),
+            Some(CodecError::UnsupportedVersion { version: 4 })
+        );
 
-        //If there arent 16 more bytes to take
-        if self.descriptor_index + 17 >= self.byte_stream.len() {
-            let chunk_byte_stream = &self.byte_stream[self.descriptor_index + 1..];
-            self.descriptor_index += (desc_entry.length + 1) as usize;
-            let mut delta_chunk = decode_chunk_safe_non_simd(descriptor, chunk_byte_stream);
-            delta_chunk_to_value_chunk(&mut delta_chunk, self.last_top);
-            self.last_top = delta_chunk[3];
-            return Some(delta_chunk);
+        //Version 2 had no flags byte after the gap mode, and version 1 no gap mode byte either
+        let mut version_2 = bytes.clone();
+        version_2[0] = 2;
+        version_2.remove(6);
+        let mut version_1 = version_2.clone();
+        version_1[0] = 1;
+        version_1.remove(5);
+        for old in [version_1, version_2] {
+            let loaded = VarintGB::from_slice(&old).unwrap();
+            assert_eq!(loaded.gap_mode(), GapMode::NonStrict);
+            assert!(!loaded.has_run_escapes());
+            assert_eq!(
+                loaded.get_values(&DescriptorTable::new()),
+                (1..=10).collect::<Vec<u32>>()
+            );
         }
 
-        /*
-        let chunk_addr = ptr::addr_of!(self.byte_stream[self.descriptor_index + 1]) as *mut __m128i;
-        let mut delta_chunk = decode_chunk_by_address(chunk_addr, desc_entry.shuffle_sequence);
-        */
+        let mut wrong_flags = bytes.clone();
+        wrong_flags[6] = 0b11;
+        assert_eq!(
+            VarintGB::from_slice(&wrong_flags).err(),
+            Some(CodecError::InvalidFlags { flags: 0b11 })
+        );
 
-        let chunk = <&[u8; 16]>::try_from(
-            &self.byte_stream[self.descriptor_index + 1..self.descriptor_index + 17],
-        )
-        .unwrap();
-        let delta_chunk = decode_chunk(chunk, desc_entry.shuffle_sequence);
+        for mode in [0, 7] {
+            let mut wrong_mode = bytes.clone();
+            wrong_mode[5] = mode;
+            assert_eq!(
+                VarintGB::from_slice(&wrong_mode).err(),
+                Some(CodecError::InvalidGapMode { mode })
+            );
+        }
+        assert!(matches!(
+            VarintGB::from_slice(&bytes[..bytes.len() - 1]),
+            Err(CodecError::Frame(FrameError::UnexpectedEnd { .. }))
+        ));
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            VarintGB::from_slice(&trailing),
+            Err(CodecError::Frame(FrameError::TrailingBytes { .. }))
+        ));
+
+        //A block length that does not end on a group boundary, without blocks to check it against
+        for block_len in [6, 1] {
+            let mut wrong_block_len = bytes.clone();
+            wrong_block_len[11] = block_len;
+            assert_eq!(
+                VarintGB::from_slice(&wrong_block_len).err(),
+                Some(CodecError::InvalidBlockHeader { block: 0 })
+            );
+        }
 
-        self.descriptor_index += (desc_entry.length + 1) as usize;
+        //Fields that fits_u16, bitmaps and seeking trust are checked against the values
+        let mut seq_factory = VarintGBFactory::new();
+        for val in [10, 70000, 140000] {
+            seq_factory.push_int(val);
+        }
+        let mut wrong_top = seq_factory.into_varint_gb().to_bytes();
+        wrong_top[7..11].copy_from_slice(&5u32.to_le_bytes());
+        assert_eq!(
+            VarintGB::from_slice(&wrong_top).err(),
+            Some(CodecError::HeaderMismatch { field: "top" })
+        );
 
-        //delta_chunk_to_value_chunk(&mut delta_chunk, self.last_top);
-        self.last_top = delta_chunk[3];
+        let mut seq_factory = VarintGBFactory::new_absolute();
+        for val in [70000, 10, 3] {
+            seq_factory.push_int(val);
+        }
+        let mut wrong_max = seq_factory.into_varint_gb().to_bytes();
+        wrong_max[15..19].copy_from_slice(&10u32.to_le_bytes());
+        assert_eq!(
+            VarintGB::from_slice(&wrong_max).err(),
+            Some(CodecError::HeaderMismatch { field: "max_gap" })
+        );
 
-        Some(delta_chunk)
-    }
-}
+        let mut seq_factory = VarintGBFactory::new_with_block_len(4);
+        for val in 1..=10 {
+            seq_factory.push_int(val * 100);
+        }
+        let block_bytes = seq_factory.into_varint_gb().to_bytes();
+        //The headers start after the fixed fields, and the last value comes first in each
+        for (block, offset) in [(0, 47), (2, 47 + 2 * 12)] {
+            let mut wrong_block = block_bytes.clone();
+            wrong_block[offset] ^= 1;
+            assert_eq!(
+                VarintGB::from_slice(&wrong_block).err(),
+                Some(CodecError::InvalidBlockHeader { block })
+            );
+        }
 
-#[inline(always)]
-fn delta_chunk_to_value_chunk(delta_chunk: &mut [u32; 4], last_top: u32) {
-    delta_chunk[0] += last_top;
-    delta_chunk[1] += delta_chunk[0];
-    delta_chunk[2] += delta_chunk[1];
-    delta_chunk[3] += delta_chunk[2];
-}
+        //Gaps that add up past u32::MAX. The stream starts after its length, and the second gap
+        //follows the descriptor and the 4 bytes of the first.
+        let mut seq_factory = VarintGBFactory::new();
+        for val in [u32::MAX - 1, u32::MAX] {
+            seq_factory.push_int(val);
+        }
+        let mut overflow = seq_factory.into_varint_gb().to_bytes();
+        assert_eq!(overflow[51 + 5], 1);
+        overflow[51 + 5] = 2;
+        assert_eq!(
+            VarintGB::from_slice(&overflow).err(),
+            Some(CodecError::ValueOverflow { offset: 0 })
+        );
 
-fn print_vec(v: &Vec<u32>) {
-    for (i, val) in v.iter().enumerate() {
-        println!("{i} : {val}");
+        //A length that does not match the byte stream fails validation
+        let mut wrong_len = bytes.clone();
+        wrong_len[1] = 11;
+        assert!(VarintGB::from_slice(&wrong_len).is_err());
+        assert!(VarintGB::from_bytes(&bytes).is_ok());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::{arch::x86_64::__m128i, hint::black_box, ptr, time::Instant};
+    #[test]
+    fn test_validate() {
+        for count in [0, 1, 4, 5, 130, 259] {
+            let mut seq_factory = VarintGBFactory::new();
+            let mut block_factory = VarintGBFactory::new_block_aligned();
+            for val in 1..=count {
+                seq_factory.push_int(val * 1000);
+                block_factory.push_int(val * 1000);
+            }
+            assert_eq!(seq_factory.into_varint_gb().validate(), Ok(()));
+            assert_eq!(block_factory.into_varint_gb().validate(), Ok(()));
+        }
 
-    use itertools::Itertools;
-    use rand::Rng;
+        let mut seq_factory = VarintGBFactory::new();
+        for val in 1..=9 {
+            seq_factory.push_int(val * 1000);
+        }
+        let mut seq = seq_factory.into_varint_gb();
 
-    use crate::{varint_gb::descriptor_length_i, varint_su::VarintSUFactory};
+        //Claim that the last value takes 4 bytes, while only 2 are left
+        seq.byte_stream.to_mut()[18] |= 0b11;
+        assert_eq!(
+            seq.validate(),
+            Err(CodecError::TruncatedGroup { offset: 18 })
+        );
+        seq.byte_stream.to_mut()[18] &= !0b10;
+        assert_eq!(seq.validate(), Ok(()));
+
+        //One value too many for the stored length
+        seq.len -= 1;
+        assert_eq!(
+            seq.validate(),
+            Err(CodecError::TrailingBytes { offset: 18 })
+        );
+        seq.len += 1;
+
+        let mut bytes = seq.byte_stream.to_vec();
+        bytes.push(0);
+        bytes.push(0);
+        seq.byte_stream = bytes.into();
+        assert_eq!(
+            seq.validate(),
+            Err(CodecError::TrailingBytes { offset: 21 })
+        );
 
-    use super::{
-        decode_chunk, decode_chunk_by_address, deltas_to_values, shuffle_sequence_from_descriptor,
-        DescriptorTable, VarintGB, VarintGBFactory,
-    };
+        let mut bytes = seq.byte_stream.to_vec();
+        bytes.truncate(10);
+        seq.byte_stream = bytes.into();
+        assert_eq!(
+            seq.validate(),
+            Err(CodecError::TruncatedGroup { offset: 9 })
+        );
+    }
 
     #[test]
-    fn test_of_sequence() {
-        let no_of_inserts = 100000;
-        let mut reference_vector = Vec::new();
-        let mut seq_factory = VarintGBFactory::new();
-
-        let mut rng = rand::thread_rng();
-        for _ in 0..no_of_inserts {
-            reference_vector.push(rng.gen_range(1..no_of_inserts) as u32);
+    fn test_decode_context() {
+        let shuffle_table = DescriptorTable::new();
+        let mut context = DecodeContext::new(&shuffle_table);
+
+        for count in [1000, 3, 0, 517] {
+            let reference_vector: Vec<u32> = (1..=count).map(|val| val * 300).collect();
+            let mut seq_factory = VarintGBFactory::new();
+            for val in reference_vector.iter() {
+                seq_factory.push_int(*val);
+            }
+            let seq = seq_factory.into_varint_gb();
+            assert_eq!(context.decode(&seq), reference_vector);
         }
+    }
 
-        reference_vector.sort();
-        reference_vector = reference_vector.iter().unique().map(|i| *i).collect();
+    #[test]
+    fn test_decode_window() {
+        let shuffle_table = DescriptorTable::new();
+        let reference_vector: Vec<u32> = (1..=5000).map(|val| val * 11).collect();
 
+        let mut seq_factory = VarintGBFactory::new();
+        let mut block_factory = VarintGBFactory::new_block_aligned();
         for val in reference_vector.iter() {
             seq_factory.push_int(*val);
+            block_factory.push_int(*val);
         }
         let seq = seq_factory.into_varint_gb();
+        let block_seq = block_factory.into_varint_gb();
+
+        for (lo, hi) in [
+            (0, 100),
+            (1000, 20000),
+            (11 * 128, 11 * 256),
+            (54990, 60000),
+            (1, 0),
+            //Empty windows that start inside a block
+            (11 * 200, 11 * 100),
+            (54990, 11),
+        ] {
+            let expected: Vec<u32> = reference_vector
+                .iter()
+                .copied()
+                .filter(|val| *val >= lo && *val <= hi)
+                .collect();
+
+            let mut window = Vec::new();
+            seq.decode_window(lo, hi, &shuffle_table, &mut window);
+            assert_eq!(window, expected);
+
+            let mut window = Vec::new();
+            block_seq.decode_window(lo, hi, &shuffle_table, &mut window);
+            assert_eq!(window, expected);
+        }
+    }
 
-        let mut shuffle_table = DescriptorTable::new();
-
-        let seq_values_iter = seq.iter(&mut shuffle_table);
-        let mut reference_iter = reference_vector.iter();
-
-        for chunk in seq_values_iter {
-            for num in chunk {
-                if let Some(ref_num) = reference_iter.next() {
-                    assert_eq!(num, *ref_num);
-                }
-            }
+    #[test]
+    fn test_lower_bound_simd() {
+        let mut rng = rand::thread_rng();
+        let mut values: Vec<u32> = (0..130).map(|_| rng.gen()).collect();
+        values.push(u32::MAX);
+        values.sort();
+
+        let mut targets: Vec<u32> = (0..1000).map(|_| rng.gen()).collect();
+        targets.extend_from_slice(&[0, 1, u32::MAX, 1 << 31, values[0], values[64]]);
+        for target in targets {
+            assert_eq!(
+                lower_bound_simd(&values, target),
+                values.partition_point(|val| *val < target)
+            );
+            assert_eq!(
+                lower_bound_simd(&values[..7], target),
+                values[..7].partition_point(|val| *val < target)
+            );
         }
-        //println!("{},{}", seq_values.len(), reference_vector.len());
+        assert_eq!(lower_bound_simd(&[], 5), 0);
     }
 
     #[test]
@@ -525,25 +3204,17 @@ mod tests {
 
     #[test]
     fn test_delta_conversion() {
-        let mut v = Vec::new();
-        v.push(1);
-        v.push(1);
-        v.push(1);
+        let v = vec![1, 1, 1];
 
         let v = deltas_to_values(&v);
-        for i in 0..3 {
-            assert_eq!(v[i] as usize, i + 1);
+        for (i, val) in v.iter().enumerate() {
+            assert_eq!(*val as usize, i + 1);
         }
     }
 
     #[test]
     fn test_len() {
-        let mut v = Vec::new();
-        v.push(1);
-        v.push(1);
-        v.push(1);
-        v.push(2);
-        v.push(5);
+        let v = [1, 1, 1, 2, 5];
 
         assert_eq!(v.len(), 5);
     }
@@ -566,21 +3237,18 @@ mod tests {
         }
 
         let chunk_16 = &chunk_vec.try_into().ok().unwrap();
-        let output = decode_chunk(
+        let shuffle_table = DescriptorTable::new();
+        let output = shuffle_table.decode_chunk(
             chunk_16,
-            DescriptorTable::new().get_shuffle_for_descriptor(descriptor),
+            &shuffle_table.get_shuffle_for_descriptor(descriptor),
         );
-        for i in output {
-            println!("{}", i);
-        }
+        assert_eq!(output, [1, 256, 4, 5]);
 
-        let output2 = decode_chunk_by_address(
-            ptr::addr_of!(chunk_16[0]) as *mut __m128i,
-            DescriptorTable::new().get_shuffle_for_descriptor(descriptor),
+        let output2 = shuffle_table.decode_chunk_by_address(
+            ptr::addr_of!(chunk_16[0]),
+            &shuffle_table.get_shuffle_for_descriptor(descriptor),
         );
-        for i in output2 {
-            println!("{}", i);
-        }
+        assert_eq!(output2, output);
     }
 
     #[test]
@@ -610,10 +3278,10 @@ mod tests {
             println!("{index} : {byte:#08b}");
         }
 
-        let mut shuffle_table = DescriptorTable::new();
+        let shuffle_table = DescriptorTable::new();
 
         println!("Nums unsafe:");
-        for chunk in varint_gb.iter(&mut shuffle_table) {
+        for chunk in varint_gb.iter(&shuffle_table) {
             for num in chunk {
                 println!("{}", num);
             }
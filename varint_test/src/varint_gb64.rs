@@ -0,0 +1,326 @@
+/*
+This refers to a 64 bit version of VarintGB, for term frequencies, positions and other values that
+can pass u32::MAX.
+
+Values are stored as gaps in groups of four, like VarintGB, but a gap takes 1-8 bytes, so its
+length needs 3 bits. The descriptor of a group is two bytes, little endian:
+
+[Unused (4 bits)] [Len 4 - 1] [Len 3 - 1] [Len 2 - 1] [Len 1 - 1]   (3 bits each)
+
+A group is decoded as two pairs of gaps. The lengths of a pair fit in 6 bits, so a table of 64
+shuffle sequences moves the bytes of a pair into two u64 lanes, with the same backends as
+VarintGB. The final group is padded with gaps of 0, so every group holds four gaps.
+*/
+
+use crate::simd::{shuffle_bytes, Backend, Shuffle};
+
+pub struct VarintGB64 {
+    pub byte_stream: Vec<u8>,
+    len: u32,
+    top: u64,
+}
+
+impl VarintGB64 {
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    //The last value, or 0 if the sequence is empty
+    pub fn top(&self) -> u64 {
+        self.top
+    }
+
+    pub fn iter<'a, 'b>(&'a self, pair_table: &'b PairTable) -> Iter<'a, 'b> {
+        Iter {
+            pair_table,
+            byte_stream: &self.byte_stream,
+            descriptor_index: 0,
+            last_top: 0,
+            chunk: [0; 4],
+            chunk_pos: 4,
+            remaining: self.len(),
+        }
+    }
+
+    pub fn get_values(&self, pair_table: &PairTable) -> Vec<u64> {
+        self.iter(pair_table).collect()
+    }
+}
+
+//Shuffle sequences and lengths for the 64 pairs of gap lengths
+pub struct PairTable {
+    shuffles: Vec<Shuffle>,
+    //Only ever set to a backend the CPU supports, which makes the shuffles safe to run
+    backend: Backend,
+}
+
+impl PairTable {
+    //A table that decodes with the fastest backend the CPU supports
+    pub fn new() -> Self {
+        Self::with_backend(Backend::detect())
+    }
+
+    //A table that decodes with the given backend. Panics if the CPU does not support it.
+    pub fn with_backend(backend: Backend) -> Self {
+        assert!(backend.is_supported(), "{backend:?} is not supported");
+
+        PairTable {
+            shuffles: (0..64).map(shuffle_sequence_from_pair).collect(),
+            backend,
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    //Decodes the two gaps of a pair from the first 16 bytes of the chunk
+    #[inline(always)]
+    pub fn decode_pair(&self, pair: u8, chunk: &[u8; 16]) -> [u64; 2] {
+        let shuffled = unsafe { shuffle_bytes(self.backend, chunk, &self.shuffles[pair as usize]) };
+        unsafe { std::mem::transmute::<[u8; 16], [u64; 2]>(shuffled) }
+    }
+}
+
+//The shuffle sequence that moves gap 1 of the pair into bytes 0-7, and gap 2 into bytes 8-15
+fn shuffle_sequence_from_pair(pair: u8) -> Shuffle {
+    let (len_1, len_2) = pair_lengths(pair);
+    let mut shuffle = Shuffle([0xFF; 16]);
+    for i in 0..len_1 {
+        shuffle.0[i] = i as u8;
+    }
+    for i in 0..len_2 {
+        shuffle.0[8 + i] = (len_1 + i) as u8;
+    }
+    shuffle
+}
+
+fn pair_lengths(pair: u8) -> (usize, usize) {
+    ((pair & 7) as usize + 1, (pair >> 3 & 7) as usize + 1)
+}
+
+//Decodes the two gaps of a pair one byte at a time, for pairs too close to the end of the stream
+//to load 16 bytes
+fn decode_pair_non_simd(pair: u8, byte_stream: &[u8]) -> [u64; 2] {
+    let (len_1, len_2) = pair_lengths(pair);
+    let mut gap_1 = [0; 8];
+    let mut gap_2 = [0; 8];
+    gap_1[..len_1].copy_from_slice(&byte_stream[..len_1]);
+    gap_2[..len_2].copy_from_slice(&byte_stream[len_1..len_1 + len_2]);
+    [u64::from_le_bytes(gap_1), u64::from_le_bytes(gap_2)]
+}
+
+pub struct Iter<'a, 'b> {
+    pair_table: &'b PairTable,
+    byte_stream: &'a [u8],
+    descriptor_index: usize,
+    last_top: u64,
+    chunk: [u64; 4],
+    chunk_pos: usize,
+    remaining: usize,
+}
+
+impl Iter<'_, '_> {
+    //Decodes the group at descriptor_index into values
+    fn next_chunk(&mut self) -> [u64; 4] {
+        let descriptor = u16::from_le_bytes([
+            self.byte_stream[self.descriptor_index],
+            self.byte_stream[self.descriptor_index + 1],
+        ]);
+        let mut offset = self.descriptor_index + 2;
+        let mut chunk = [0; 4];
+
+        for (half, pair) in [descriptor as u8 & 63, (descriptor >> 6) as u8 & 63]
+            .into_iter()
+            .enumerate()
+        {
+            let gaps = if offset + 16 <= self.byte_stream.len() {
+                let bytes = <&[u8; 16]>::try_from(&self.byte_stream[offset..offset + 16]).unwrap();
+                self.pair_table.decode_pair(pair, bytes)
+            } else {
+                decode_pair_non_simd(pair, &self.byte_stream[offset..])
+            };
+
+            for (i, gap) in gaps.into_iter().enumerate() {
+                self.last_top += gap;
+                chunk[half * 2 + i] = self.last_top;
+            }
+            let (len_1, len_2) = pair_lengths(pair);
+            offset += len_1 + len_2;
+        }
+
+        self.descriptor_index = offset;
+        chunk
+    }
+}
+
+impl Iterator for Iter<'_, '_> {
+    type Item = u64;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        if self.chunk_pos == 4 {
+            self.chunk = self.next_chunk();
+            self.chunk_pos = 0;
+        }
+
+        let val = self.chunk[self.chunk_pos];
+        self.chunk_pos += 1;
+        self.remaining -= 1;
+        Some(val)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_, '_> {}
+
+pub struct VarintGB64Factory {
+    byte_stream: Vec<u8>,
+    top: u64,
+    descriptor_index: usize,
+    index_in_chunk: u8,
+    len: u32,
+}
+
+impl VarintGB64Factory {
+    pub fn new() -> Self {
+        VarintGB64Factory {
+            byte_stream: Vec::new(),
+            top: 0,
+            descriptor_index: 0,
+            index_in_chunk: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push_int(&mut self, x: u64) {
+        assert!(x >= self.top, "values must be pushed in increasing order");
+        self.len += 1;
+        let gap = x - self.top;
+        self.top = x;
+        self.push_gap(gap);
+    }
+
+    fn push_gap(&mut self, gap: u64) {
+        //If starting a new group, add the two descriptor bytes
+        if self.index_in_chunk == 0 {
+            self.descriptor_index = self.byte_stream.len();
+            self.byte_stream.extend_from_slice(&[0, 0]);
+        }
+
+        //Bytes needed for the gap, at least 1 for a gap of 0
+        let gap_len = (8 - gap.leading_zeros() as usize / 8).max(1);
+        self.byte_stream
+            .extend_from_slice(&gap.to_le_bytes()[..gap_len]);
+
+        let shift = 3 * self.index_in_chunk;
+        let mut descriptor = u16::from_le_bytes([
+            self.byte_stream[self.descriptor_index],
+            self.byte_stream[self.descriptor_index + 1],
+        ]);
+        descriptor |= ((gap_len - 1) as u16) << shift;
+        self.byte_stream[self.descriptor_index..self.descriptor_index + 2]
+            .copy_from_slice(&descriptor.to_le_bytes());
+
+        self.index_in_chunk = (self.index_in_chunk + 1) % 4;
+    }
+
+    pub fn into_varint_gb64(mut self) -> VarintGB64 {
+        while self.index_in_chunk != 0 {
+            self.push_gap(0);
+        }
+
+        VarintGB64 {
+            byte_stream: self.byte_stream,
+            len: self.len,
+            top: self.top,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use crate::simd::Backend;
+
+    use super::{decode_pair_non_simd, PairTable, VarintGB64Factory};
+
+    #[test]
+    fn test_pair_table() {
+        let pair_table = PairTable::new();
+        let mut rng = rand::thread_rng();
+        for pair in 0..64 {
+            let chunk: [u8; 16] = rng.gen();
+            assert_eq!(
+                pair_table.decode_pair(pair, &chunk),
+                decode_pair_non_simd(pair, &chunk)
+            );
+        }
+
+        //Lengths 3 and 8
+        let chunk = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 0, 0, 0, 0, 0];
+        assert_eq!(
+            pair_table.decode_pair(0b111_010, &chunk),
+            [0x030201, 0x0B0A090807060504]
+        );
+    }
+
+    #[test]
+    fn test_compressions() {
+        let mut rng = rand::thread_rng();
+
+        for len in [0, 1, 2, 3, 4, 5, 7, 8, 1000] {
+            let mut reference_vector = Vec::new();
+            let mut value: u64 = 0;
+            for _ in 0..len {
+                //Gaps of 1 to 7 bytes, so values pass 2^32 early
+                let bits = rng.gen_range(0..50);
+                value += rng.gen_range(0..=1 << bits);
+                reference_vector.push(value);
+            }
+
+            let mut fact = VarintGB64Factory::new();
+            for val in reference_vector.iter() {
+                fact.push_int(*val);
+            }
+            let seq = fact.into_varint_gb64();
+            assert_eq!(seq.len(), len);
+
+            for backend in [Backend::Scalar, Backend::detect()] {
+                let pair_table = PairTable::with_backend(backend);
+                assert_eq!(seq.iter(&pair_table).len(), len);
+                assert_eq!(seq.get_values(&pair_table), reference_vector);
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_values() {
+        let reference_vector = [
+            0,
+            u32::MAX as u64,
+            u32::MAX as u64 + 1,
+            1 << 40,
+            (1 << 56) - 1,
+            1 << 56,
+            u64::MAX - 1,
+            u64::MAX,
+        ];
+        let mut fact = VarintGB64Factory::new();
+        for val in reference_vector.iter() {
+            fact.push_int(*val);
+        }
+        let seq = fact.into_varint_gb64();
+
+        assert_eq!(seq.top(), u64::MAX);
+        assert_eq!(seq.get_values(&PairTable::new()), reference_vector);
+    }
+}
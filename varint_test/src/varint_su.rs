@@ -1,11 +1,31 @@
-use std::mem;
+use std::{
+    borrow::Cow,
+    io::{self, Read, Write},
+    ops::Range,
+};
 
-pub struct VarintSU {
-    pub bytes: Box<[u8]>,
+use crate::codec::{CursorMark, GapMode};
+use crate::error::CodecError;
+use crate::frame::FrameReader;
+
+//The bytes are borrowed when the sequence is loaded in place with from_slice, and owned otherwise
+pub struct VarintSU<'a> {
+    pub bytes: Cow<'a, [u8]>,
     len: u32,
+    restarts: Box<[Restart]>,
+    gap_mode: GapMode,
+}
+
+//A point in the stream where the value is encoded as a gap from 0 instead of from the previous
+//value, so decoding can start there, and corruption before it does not carry over
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Restart {
+    pub byte_offset: u32,
+    pub index: u32,
+    pub value: u32,
 }
 
-impl VarintSU {
+impl VarintSU<'_> {
     pub fn len(&self) -> usize {
         self.len as usize
     }
@@ -15,14 +35,281 @@ impl VarintSU {
             int_vec: &self.bytes,
             next_index: 0,
             last_value: 0,
+            index: 0,
+            restarts: &self.restarts,
+            next_restart: 0,
+            gap_adjustment: self.gap_mode.adjustment() as usize,
+            absolute: self.gap_mode == GapMode::Absolute,
+        }
+    }
+
+    pub fn gap_mode(&self) -> GapMode {
+        self.gap_mode
+    }
+
+    pub fn restarts(&self) -> &[Restart] {
+        &self.restarts
+    }
+
+    //Iterates from the given restart point to the end, without decoding anything before it
+    pub fn iter_from_restart(&self, restart: usize) -> Iter<'_> {
+        Iter {
+            int_vec: &self.bytes,
+            next_index: self.restarts[restart].byte_offset as usize,
+            last_value: 0,
+            index: self.restarts[restart].index,
+            restarts: &self.restarts,
+            next_restart: restart,
+            gap_adjustment: self.gap_mode.adjustment() as usize,
+            absolute: self.gap_mode == GapMode::Absolute,
+        }
+    }
+
+    //The last restart point at or before the first value >= target
+    pub fn restart_for_value(&self, target: u32) -> Option<usize> {
+        assert!(
+            self.gap_mode != GapMode::Absolute,
+            "absolute values are not sorted"
+        );
+        let restart = self
+            .restarts
+            .partition_point(|restart| restart.value <= target);
+        restart.checked_sub(1)
+    }
+
+    //The value at the given index, or None if it is past the end
+    pub fn get(&self, index: usize) -> Option<u32> {
+        self.iter_from_index(index).next().map(|val| val as u32)
+    }
+
+    //The values at the indices in range. The range is cut off at the end of the sequence.
+    pub fn get_range(&self, range: Range<usize>) -> Vec<u32> {
+        let end = range.end.min(self.len());
+        if range.start >= end {
+            return Vec::new();
+        }
+        self.iter_from_index(range.start)
+            .take(end - range.start)
+            .map(|val| val as u32)
+            .collect()
+    }
+
+    //An iterator whose next value is the one at index. Decoding starts at the last restart point
+    //at or before it, so at most restart_interval values are decoded to reach it.
+    fn iter_from_index(&self, index: usize) -> Iter<'_> {
+        let restart = self
+            .restarts
+            .partition_point(|restart| restart.index as usize <= index);
+        let (mut iter, skip) = match restart.checked_sub(1) {
+            Some(restart) => (
+                self.iter_from_restart(restart),
+                index - self.restarts[restart].index as usize,
+            ),
+            None => (self.iter(), index),
+        };
+
+        if skip > 0 {
+            iter.nth(skip - 1);
+        }
+        iter
+    }
+
+    //Checks that no value runs past the end of the bytes, that they hold len values, and that
+    //every restart point starts the value it claims to. Sequences that pass can be iterated
+    //without panicking.
+    pub fn validate(&self) -> Result<(), CodecError> {
+        let mut values = 0;
+        let mut restart = 0;
+        let mut index = 0;
+
+        while index < self.bytes.len() {
+            if restart < self.restarts.len() && self.restarts[restart].byte_offset as usize == index
+            {
+                if self.restarts[restart].index as usize != values {
+                    return Err(CodecError::InvalidRestart { restart });
+                }
+                restart += 1;
+            }
+
+            //A value is at most four continuation bytes, followed by one more byte
+            let start = index;
+            for _ in 0..4 {
+                if self.bytes[index] < 128 {
+                    break;
+                }
+                index += 1;
+                if index == self.bytes.len() {
+                    return Err(CodecError::TruncatedGroup { offset: start });
+                }
+            }
+            index += 1;
+            values += 1;
+        }
+
+        if values != self.len() {
+            return Err(CodecError::LengthMismatch {
+                expected: self.len(),
+                found: values,
+            });
+        }
+        if restart < self.restarts.len() {
+            return Err(CodecError::InvalidRestart { restart });
+        }
+
+        for restart in 0..self.restarts.len() {
+            let value = self.iter_from_restart(restart).next();
+            if value != Some(self.restarts[restart].value as usize) {
+                return Err(CodecError::InvalidRestart { restart });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/*
+Serialized layout, with every u32 little endian:
+
+[Version (u8)] [Len] [Gap mode (u8)]
+[Number of restarts] [Byte offset, index, value] for each restart
+[Bytes length] [Bytes]
+*/
+pub const FORMAT_VERSION: u8 = 1;
+
+impl<'a> VarintSU<'a> {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            1 + 4 + 1 + 4 * (1 + 3 * self.restarts.len() + 1) + self.bytes.len(),
+        );
+        bytes.push(FORMAT_VERSION);
+        bytes.extend_from_slice(&self.len.to_le_bytes());
+        bytes.push(self.gap_mode.to_u8());
+
+        bytes.extend_from_slice(&(self.restarts.len() as u32).to_le_bytes());
+        for restart in self.restarts.iter() {
+            bytes.extend_from_slice(&restart.byte_offset.to_le_bytes());
+            bytes.extend_from_slice(&restart.index.to_le_bytes());
+            bytes.extend_from_slice(&restart.value.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.bytes.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.bytes);
+        bytes
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    //Loads a sequence written by to_bytes, borrowing the bytes from the input.
+    //The sequence is validated, so it is safe to iterate.
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self, CodecError> {
+        let mut reader = FrameReader::new(bytes);
+
+        let version = reader.read_u8()?;
+        if version != FORMAT_VERSION {
+            return Err(CodecError::UnsupportedVersion { version });
+        }
+
+        let len = reader.read_u32()?;
+        let mode = reader.read_u8()?;
+        let gap_mode = GapMode::from_u8(mode).ok_or(CodecError::InvalidGapMode { mode })?;
+
+        //Every restart takes 12 bytes, so a corrupt count fails before allocating for it
+        let restart_count = reader.read_u32()? as usize;
+        let mut restarts = Vec::with_capacity(restart_count.min(reader.remaining() / 12));
+        for _ in 0..restart_count {
+            restarts.push(Restart {
+                byte_offset: reader.read_u32()?,
+                index: reader.read_u32()?,
+                value: reader.read_u32()?,
+            });
+        }
+
+        let bytes_len = reader.read_u32()? as usize;
+        let bytes = reader.read_bytes(bytes_len)?;
+        reader.finish()?;
+
+        let seq = VarintSU {
+            bytes: Cow::Borrowed(bytes),
+            len,
+            restarts: restarts.into_boxed_slice(),
+            gap_mode,
+        };
+        seq.validate()?;
+        Ok(seq)
+    }
+
+    //Copies the bytes, if they are borrowed, so the sequence no longer refers to the input
+    pub fn into_owned(self) -> VarintSU<'static> {
+        VarintSU {
+            bytes: Cow::Owned(self.bytes.into_owned()),
+            ..self
         }
     }
 }
 
+impl VarintSU<'static> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        VarintSU::from_slice(bytes).map(VarintSU::into_owned)
+    }
+
+    //Reads a sequence written by write_to, until the end of the reader
+    pub fn from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
 pub struct Iter<'a> {
     int_vec: &'a [u8],
     next_index: usize,
     last_value: usize,
+    //Index of the next value
+    index: u32,
+    restarts: &'a [Restart],
+    next_restart: usize,
+    gap_adjustment: usize,
+    //Absolute values are not added to the previous value
+    absolute: bool,
+}
+
+impl Iter<'_> {
+    //Returns the first value >= target at or after the current position. If a restart point at or
+    //before the target lies ahead, decoding jumps straight to it.
+    pub fn next_geq(&mut self, target: u32) -> Option<usize> {
+        assert!(!self.absolute, "absolute values are not sorted");
+        let restart = self
+            .restarts
+            .partition_point(|restart| restart.value <= target);
+        if restart > self.next_restart {
+            self.next_index = self.restarts[restart - 1].byte_offset as usize;
+            self.index = self.restarts[restart - 1].index;
+            self.next_restart = restart - 1;
+        }
+
+        self.find(|val| *val >= target as usize)
+    }
+
+    //Saves the position, so rewind can return to it without decoding from the start
+    pub fn mark(&self) -> CursorMark {
+        CursorMark {
+            byte_offset: self.next_index,
+            last_value: self.last_value as u32,
+            ordinal: self.index,
+        }
+    }
+
+    //Continues from a position saved by mark, before or after the current one
+    pub fn rewind(&mut self, mark: CursorMark) {
+        self.next_index = mark.byte_offset;
+        self.last_value = mark.last_value as usize;
+        self.index = mark.ordinal;
+        self.next_restart = self
+            .restarts
+            .partition_point(|restart| (restart.byte_offset as usize) < mark.byte_offset);
+    }
 }
 
 impl<'a> Iterator for Iter<'a> {
@@ -33,6 +320,13 @@ impl<'a> Iterator for Iter<'a> {
             return None;
         }
 
+        if self.next_restart < self.restarts.len()
+            && self.restarts[self.next_restart].byte_offset as usize == self.next_index
+        {
+            self.last_value = 0;
+            self.next_restart += 1;
+        }
+
         let mut x: usize = 0;
         let mut p = 1;
         let mut b_word = self.int_vec[self.next_index] as usize;
@@ -48,9 +342,14 @@ impl<'a> Iterator for Iter<'a> {
             b_word = self.int_vec[self.next_index] as usize;
         }
         //println!("{b_word}");
-        x = x + (b_word + 1) * p; //+ self.last_value;
-                                  //self.last_value = x;
+        //The decoded number is one more than the stored one, which is the gap in strict mode
+        x += (b_word + 1) * p + self.gap_adjustment - 1;
+        if !self.absolute {
+            x += self.last_value;
+        }
+        self.last_value = x;
         self.next_index += 1;
+        self.index += 1;
 
         Some(x)
     }
@@ -60,6 +359,9 @@ pub struct VarintSUFactory {
     pub vec: Vec<u8>,
     top: u32,
     len: u32,
+    restart_interval: u32,
+    restarts: Vec<Restart>,
+    gap_mode: GapMode,
 }
 impl VarintSUFactory {
     pub fn new() -> Self {
@@ -67,15 +369,43 @@ impl VarintSUFactory {
             vec: Vec::new(),
             top: 0,
             len: 0,
+            restart_interval: 0,
+            restarts: Vec::new(),
+            gap_mode: GapMode::Strict,
         }
     }
 
-    pub fn into_varint_su(&mut self) -> VarintSU {
-        let vec = mem::replace(&mut self.vec, Vec::new());
+    //A factory that accepts repeated values, which a strict factory drops
+    pub fn new_non_strict() -> Self {
+        VarintSUFactory {
+            gap_mode: GapMode::NonStrict,
+            ..Self::new()
+        }
+    }
 
+    //A factory that stores the values themselves instead of gaps, so they can come in any order
+    pub fn new_absolute() -> Self {
+        VarintSUFactory {
+            gap_mode: GapMode::Absolute,
+            ..Self::new()
+        }
+    }
+
+    //A factory that makes every restart_interval'th value, starting with the first, a restart point
+    pub fn new_with_restarts(restart_interval: u32) -> Self {
+        assert!(restart_interval > 0);
+        VarintSUFactory {
+            restart_interval,
+            ..Self::new()
+        }
+    }
+
+    pub fn into_varint_su(self) -> VarintSU<'static> {
         VarintSU {
-            bytes: vec.into_boxed_slice(),
+            bytes: Cow::Owned(self.vec),
             len: self.len,
+            restarts: self.restarts.into_boxed_slice(),
+            gap_mode: self.gap_mode,
         }
     }
 
@@ -87,12 +417,30 @@ impl VarintSUFactory {
 
     //if x >= 128, it can be written as x = c*128+d, where d < 128. We write d in a byte, and write c, recursively
     pub fn push_int(&mut self, int: u32) {
-        if int == self.top {
+        if self.gap_mode == GapMode::Strict && int == self.top {
             return;
         }
+        let absolute = self.gap_mode == GapMode::Absolute;
+        assert!(
+            absolute || int >= self.top,
+            "values must be pushed in increasing order"
+        );
+
+        //At a restart point, the value is encoded as a gap from 0
+        let mut base = if absolute { 0 } else { self.top };
+        if self.restart_interval > 0
+            && self.len == self.restarts.len() as u32 * self.restart_interval
+        {
+            self.restarts.push(Restart {
+                byte_offset: self.vec.len() as u32,
+                index: self.len,
+                value: int,
+            });
+            base = 0;
+        }
 
         self.len += 1;
-        let mut x = int - self.top - 1;
+        let mut x = int - base - self.gap_mode.adjustment();
 
         for _ in 0..4 {
             if x < 128 {
@@ -124,6 +472,297 @@ fn test_compressions() {
     assert_eq!(iterator.next().unwrap(), 17003);
 }
 
+#[test]
+fn test_restarts() {
+    let reference_vector: Vec<u32> = (1..=1000).map(|val| val * 37).collect();
+    let mut fact = VarintSUFactory::new_with_restarts(64);
+    for val in reference_vector.iter() {
+        fact.push_int(*val);
+    }
+    let varint = fact.into_varint_su();
+
+    assert_eq!(varint.restarts().len(), 16);
+    let values: Vec<u32> = varint.iter().map(|val| val as u32).collect();
+    assert_eq!(values, reference_vector);
+
+    let restart = varint.restart_for_value(37 * 300).unwrap();
+    assert_eq!(restart, 4);
+    assert_eq!(varint.restarts()[restart].index, 256);
+    let values: Vec<u32> = varint
+        .iter_from_restart(restart)
+        .map(|val| val as u32)
+        .collect();
+    assert_eq!(values, reference_vector[256..]);
+
+    //Corrupting a byte in the first block does not affect values from the next restart on
+    let mut bytes = varint.bytes.to_vec();
+    bytes[10] ^= 1;
+    let corrupted = VarintSU {
+        bytes: bytes.into(),
+        ..varint
+    };
+    let values: Vec<u32> = corrupted.iter().map(|val| val as u32).collect();
+    assert_ne!(values[..64], reference_vector[..64]);
+    assert_eq!(values[64..], reference_vector[64..]);
+}
+
+#[test]
+fn test_next_geq() {
+    let reference_vector: Vec<u32> = (1..=1000).map(|val| val * 37).collect();
+    for restart_interval in [0, 1, 64] {
+        let mut fact = if restart_interval == 0 {
+            VarintSUFactory::new()
+        } else {
+            VarintSUFactory::new_with_restarts(restart_interval)
+        };
+        for val in reference_vector.iter() {
+            fact.push_int(*val);
+        }
+        let varint = fact.into_varint_su();
+
+        let mut iter = varint.iter();
+        assert_eq!(iter.next_geq(0), Some(37));
+        assert_eq!(iter.next_geq(37), Some(74));
+        assert_eq!(iter.next_geq(37 * 300 + 1), Some(37 * 301));
+        assert_eq!(iter.next(), Some(37 * 302));
+        assert_eq!(iter.next_geq(37 * 302), Some(37 * 303));
+        assert_eq!(iter.next_geq(37 * 900), Some(37 * 900));
+        assert_eq!(iter.next_geq(37 * 1000 + 1), None);
+
+        //Seeking backwards does not go back
+        let mut iter = varint.iter();
+        assert_eq!(iter.next_geq(37 * 500), Some(37 * 500));
+        assert_eq!(iter.next_geq(37 * 10), Some(37 * 501));
+    }
+}
+
+#[test]
+fn test_mark_rewind() {
+    let reference_vector: Vec<u32> = (1..=500).map(|val| val * 101).collect();
+    for restart_interval in [0, 1, 64] {
+        let mut fact = if restart_interval == 0 {
+            VarintSUFactory::new()
+        } else {
+            VarintSUFactory::new_with_restarts(restart_interval)
+        };
+        for val in reference_vector.iter() {
+            fact.push_int(*val);
+        }
+        let varint = fact.into_varint_su();
+
+        let mut iter = varint.iter();
+        assert_eq!(iter.next_geq(101 * 100), Some(101 * 100));
+        let mark = iter.mark();
+        assert_eq!(mark.ordinal, 100);
+        assert_eq!(iter.next_geq(101 * 400), Some(101 * 400));
+        iter.rewind(mark);
+        assert_eq!(iter.next(), Some(101 * 101));
+        assert_eq!(iter.mark().ordinal, 101);
+
+        //Rewinding forwards, past restart points
+        let mut other = varint.iter();
+        other.rewind(iter.mark());
+        let rest: Vec<usize> = other.collect();
+        let expected: Vec<usize> = reference_vector[101..]
+            .iter()
+            .map(|val| *val as usize)
+            .collect();
+        assert_eq!(rest, expected);
+    }
+}
+
+#[test]
+fn test_get() {
+    let reference_vector: Vec<u32> = (1..=500).map(|val| val * 101).collect();
+    for restart_interval in [0, 1, 64] {
+        let mut fact = if restart_interval == 0 {
+            VarintSUFactory::new()
+        } else {
+            VarintSUFactory::new_with_restarts(restart_interval)
+        };
+        for val in reference_vector.iter() {
+            fact.push_int(*val);
+        }
+        let varint = fact.into_varint_su();
+
+        for (index, val) in reference_vector.iter().enumerate() {
+            assert_eq!(varint.get(index), Some(*val));
+        }
+        assert_eq!(varint.get(500), None);
+
+        assert_eq!(varint.get_range(0..500), reference_vector);
+        assert_eq!(varint.get_range(63..65), reference_vector[63..65]);
+        assert_eq!(varint.get_range(450..600), reference_vector[450..]);
+        assert!(varint.get_range(10..10).is_empty());
+        assert!(varint.get_range(600..700).is_empty());
+    }
+}
+
+#[test]
+fn test_non_strict() {
+    let reference_vector = [0, 0, 5, 5, 5, 200, 17003, 17003, u32::MAX];
+
+    let mut fact = VarintSUFactory::new_non_strict();
+    for val in reference_vector.iter() {
+        fact.push_int(*val);
+    }
+    let varint = fact.into_varint_su();
+    assert_eq!(varint.gap_mode(), GapMode::NonStrict);
+    assert_eq!(varint.len(), reference_vector.len());
+
+    let values: Vec<u32> = varint.iter().map(|val| val as u32).collect();
+    assert_eq!(values, reference_vector);
+
+    //The strict factory drops repeated values
+    let mut fact = VarintSUFactory::new();
+    for val in reference_vector.iter() {
+        fact.push_int(*val);
+    }
+    let values: Vec<u32> = fact.into_varint_su().iter().map(|val| val as u32).collect();
+    assert_eq!(values, [5, 200, 17003, u32::MAX]);
+}
+
+#[test]
+#[should_panic(expected = "absolute values are not sorted")]
+fn test_absolute_next_geq() {
+    let mut fact = VarintSUFactory::new_absolute();
+    for val in [100, 5, 3] {
+        fact.push_int(val);
+    }
+    fact.into_varint_su().iter().next_geq(4);
+}
+
+#[test]
+fn test_absolute() {
+    let reference_vector = [5, 5, 0, 1, 300, 2, 70000, 0, u32::MAX, 3, 3, 200];
+    for restart_interval in [0, 4] {
+        let mut fact = VarintSUFactory::new_absolute();
+        fact.restart_interval = restart_interval;
+        for val in reference_vector.iter() {
+            fact.push_int(*val);
+        }
+        let varint = fact.into_varint_su();
+        assert_eq!(varint.gap_mode(), GapMode::Absolute);
+        assert_eq!(varint.len(), reference_vector.len());
+
+        let values: Vec<u32> = varint.iter().map(|val| val as u32).collect();
+        assert_eq!(values, reference_vector);
+        assert_eq!(varint.get(9), Some(3));
+
+        let loaded = VarintSU::from_bytes(&varint.to_bytes()).unwrap();
+        assert_eq!(loaded.gap_mode(), GapMode::Absolute);
+        assert_eq!(loaded.get_range(0..12), reference_vector);
+    }
+}
+
+#[test]
+fn test_serialization() {
+    use crate::frame::FrameError;
+
+    let reference_vector: Vec<u32> = (1..=1000).map(|val| val * val).collect();
+    for restart_interval in [0, 64] {
+        let mut fact = if restart_interval == 0 {
+            VarintSUFactory::new()
+        } else {
+            VarintSUFactory::new_with_restarts(restart_interval)
+        };
+        for val in reference_vector.iter() {
+            fact.push_int(*val);
+        }
+        let varint = fact.into_varint_su();
+        let bytes = varint.to_bytes();
+
+        let loaded = VarintSU::from_slice(&bytes).unwrap();
+        assert!(matches!(loaded.bytes, Cow::Borrowed(_)));
+        assert_eq!(loaded.restarts(), varint.restarts());
+        let values: Vec<u32> = loaded.iter().map(|val| val as u32).collect();
+        assert_eq!(values, reference_vector);
+
+        let mut written = Vec::new();
+        varint.write_to(&mut written).unwrap();
+        assert_eq!(written, bytes);
+        let loaded = VarintSU::from_reader(&mut written.as_slice()).unwrap();
+        assert_eq!(loaded.len(), reference_vector.len());
+    }
+
+    let mut fact = VarintSUFactory::new_non_strict();
+    for val in [3, 3, 500] {
+        fact.push_int(val);
+    }
+    let varint = fact.into_varint_su();
+    let bytes = varint.to_bytes();
+    let loaded = VarintSU::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded.gap_mode(), GapMode::NonStrict);
+    assert_eq!(loaded.iter().collect::<Vec<usize>>(), [3, 3, 500]);
+
+    let mut wrong_version = bytes.clone();
+    wrong_version[0] = 0;
+    assert_eq!(
+        VarintSU::from_slice(&wrong_version).err(),
+        Some(CodecError::UnsupportedVersion { version: 0 })
+    );
+    let mut wrong_mode = bytes.clone();
+    wrong_mode[5] = 7;
+    assert_eq!(
+        VarintSU::from_slice(&wrong_mode).err(),
+        Some(CodecError::InvalidGapMode { mode: 7 })
+    );
+    assert!(matches!(
+        VarintSU::from_slice(&bytes[..bytes.len() - 1]),
+        Err(CodecError::Frame(FrameError::UnexpectedEnd { .. }))
+    ));
+}
+
+#[test]
+fn test_validate() {
+    let mut fact = VarintSUFactory::new_with_restarts(2);
+    for val in [1, 300, 301, 70000, 70001] {
+        fact.push_int(val);
+    }
+    let varint = fact.into_varint_su();
+    assert_eq!(varint.validate(), Ok(()));
+
+    //The last value is cut off in the middle of its continuation bytes
+    let mut bytes = varint.bytes.to_vec();
+    bytes.pop();
+    let last_start = varint.restarts()[2].byte_offset as usize;
+    let truncated = VarintSU {
+        bytes: bytes.into(),
+        len: 4,
+        restarts: varint.restarts.clone(),
+        gap_mode: varint.gap_mode,
+    };
+    assert_eq!(
+        truncated.validate(),
+        Err(CodecError::TruncatedGroup { offset: last_start })
+    );
+
+    let wrong_len = VarintSU {
+        bytes: varint.bytes.clone(),
+        len: 6,
+        restarts: varint.restarts.clone(),
+        gap_mode: varint.gap_mode,
+    };
+    assert_eq!(
+        wrong_len.validate(),
+        Err(CodecError::LengthMismatch {
+            expected: 6,
+            found: 5
+        })
+    );
+
+    let mut restarts = varint.restarts.to_vec();
+    restarts[1].value += 1;
+    let wrong_restart = VarintSU {
+        restarts: restarts.into_boxed_slice(),
+        ..varint
+    };
+    assert_eq!(
+        wrong_restart.validate(),
+        Err(CodecError::InvalidRestart { restart: 1 })
+    );
+}
+
 #[test]
 fn test_bench() {
     println!("{}", 357 & 127);